@@ -0,0 +1,31 @@
+//! Lists the pngs in `src/tex` for `resources::TEXTURES`, so adding one
+//! there is all it takes to pack it into the atlas.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let tex_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src/tex");
+    println!("cargo:rerun-if-changed={}", tex_dir.display());
+
+    let mut pngs = Vec::new();
+    for entry in fs::read_dir(&tex_dir).expect("could not read src/tex") {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            pngs.push(path);
+        }
+    }
+    // sorted like `atlas::read_pngs`, which hashes them in the same order
+    pngs.sort();
+
+    let mut code = String::from("&[\n");
+    for path in pngs {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let name = path.file_stem().unwrap().to_string_lossy();
+        code += &format!("    ({:?}, include_bytes!({:?})),\n", name, path.display().to_string());
+    }
+    code += "]\n";
+
+    fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("textures.rs"), code).unwrap();
+}
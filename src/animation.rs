@@ -0,0 +1,239 @@
+use crate::camera::Perspective;
+use crate::player::Player;
+use crate::resources::SpriteFrame;
+
+/// A run of frames along one row of a sprite sheet.
+pub struct Animation {
+    pub row: u32,
+    pub frames: u32,
+    pub fps: f32,
+    pub looping: bool,
+}
+
+impl Animation {
+    fn frame(&self, time: f32) -> u32 {
+        let frame = (time * self.fps) as u32;
+        if self.looping {
+            frame % self.frames
+        } else {
+            frame.min(self.frames - 1)
+        }
+    }
+
+    fn finished(&self, time: f32) -> bool {
+        !self.looping && time * self.fps >= self.frames as f32
+    }
+}
+
+// player.png is a 4x7 grid of 16x16 frames facing right, one animation per
+// row
+const PLAYER_SHEET: &str = "player";
+const PLAYER_SHEET_GRID: (u32, u32) = (4, 7);
+
+const IDLE: Animation = Animation { row: 0, frames: 2, fps: 2.0, looping: true };
+const WALK: Animation = Animation { row: 1, frames: 4, fps: 10.0, looping: true };
+const JUMP_RISE: Animation = Animation { row: 2, frames: 2, fps: 8.0, looping: false };
+const FALL: Animation = Animation { row: 3, frames: 2, fps: 8.0, looping: true };
+const LAND: Animation = Animation { row: 4, frames: 2, fps: 12.0, looping: false };
+const TURN: Animation = Animation { row: 5, frames: 2, fps: 16.0, looping: false };
+const CLIMB: Animation = Animation { row: 6, frames: 4, fps: 8.0, looping: true };
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PlayerState {
+    Idle,
+    Walk,
+    JumpRise,
+    Fall,
+    Land,
+    Turn,
+    Climb,
+}
+
+impl PlayerState {
+    fn animation(self) -> &'static Animation {
+        match self {
+            PlayerState::Idle => &IDLE,
+            PlayerState::Walk => &WALK,
+            PlayerState::JumpRise => &JUMP_RISE,
+            PlayerState::Fall => &FALL,
+            PlayerState::Land => &LAND,
+            PlayerState::Turn => &TURN,
+            PlayerState::Climb => &CLIMB,
+        }
+    }
+}
+
+// the world direction that shows up as "right" on screen
+fn screen_right(perspective: Perspective) -> (i32, i32) {
+    use Perspective::*;
+
+    match perspective {
+        S => (1, 0),
+        N => (-1, 0),
+        E => (0, 1),
+        W => (0, -1),
+    }
+}
+
+pub struct PlayerAnimator {
+    state: PlayerState,
+    time: f32,
+
+    // kept in world space, so turning the camera around mirrors the sprite
+    facing: (i32, i32),
+    flip_x: bool,
+}
+
+impl PlayerAnimator {
+    pub fn new() -> PlayerAnimator {
+        PlayerAnimator {
+            state: PlayerState::Fall,
+            time: 0.0,
+
+            facing: screen_right(Perspective::S),
+            flip_x: false,
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32, player: &Player, perspective: Perspective) {
+        self.time += delta;
+
+        let right = screen_right(perspective);
+        let moving = player.movement.moving_left() || player.movement.moving_right();
+
+        let flip_x = if moving {
+            let sign = if player.movement.moving_left() { -1 } else { 1 };
+            self.facing = (sign*right.0, sign*right.1);
+            sign < 0
+        } else {
+            match self.facing.0*right.0 + self.facing.1*right.1 {
+                0 => {
+                    // a quarter turn leaves us facing the camera; keep
+                    // looking the same way on screen instead
+                    let sign = if self.flip_x { -1 } else { 1 };
+                    self.facing = (sign*right.0, sign*right.1);
+                    self.flip_x
+                },
+                dot => dot < 0,
+            }
+        };
+        let turned = flip_x != self.flip_x;
+        self.flip_x = flip_x;
+
+        use PlayerState::*;
+
+        let animation = self.state.animation();
+        let next = if player.climbing() {
+            Climb
+        } else if player.standing_on.is_none() {
+            if player.z_vel > 0.0 { JumpRise } else { Fall }
+        } else if let JumpRise | Fall = self.state {
+            Land
+        } else if turned && moving {
+            Turn
+        } else if let (Land, false) | (Turn, false) = (self.state, animation.finished(self.time)) {
+            self.state
+        } else if moving {
+            Walk
+        } else {
+            Idle
+        };
+
+        if next != self.state || turned && next == Turn {
+            self.state = next;
+            self.time = 0.0;
+        }
+    }
+
+    pub fn frame(&self) -> SpriteFrame {
+        let animation = self.state.animation();
+
+        SpriteFrame {
+            texture: PLAYER_SHEET,
+            grid: PLAYER_SHEET_GRID,
+            cell: (animation.frame(self.time), animation.row),
+            flip_x: self.flip_x,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(animator: &mut PlayerAnimator, delta: f32, player: &Player) -> PlayerState {
+        animator.tick(delta, player, Perspective::S);
+        animator.state
+    }
+
+    #[test]
+    fn player_states() {
+        use PlayerState::*;
+
+        let mut animator = PlayerAnimator::new();
+        let mut player = Player::new((0.0, 0.0, 0.0));
+
+        // landing plays out before going idle
+        player.standing_on = Some(0);
+        assert_eq!(tick(&mut animator, 0.0, &player), Land);
+        assert_eq!(tick(&mut animator, 0.1, &player), Land);
+        assert_eq!(tick(&mut animator, 0.1, &player), Idle);
+
+        player.movement.press_right();
+        assert_eq!(tick(&mut animator, 0.1, &player), Walk);
+
+        player.standing_on = None;
+        player.z_vel = 5.0;
+        assert_eq!(tick(&mut animator, 0.1, &player), JumpRise);
+        player.z_vel = -1.0;
+        assert_eq!(tick(&mut animator, 0.1, &player), Fall);
+
+        player.standing_on = Some(0);
+        assert_eq!(tick(&mut animator, 0.1, &player), Land);
+        assert_eq!(tick(&mut animator, 0.2, &player), Walk);
+
+        // turning around plays the turn first
+        player.movement.release_right();
+        player.movement.press_left();
+        assert_eq!(tick(&mut animator, 0.1, &player), Turn);
+        assert!(animator.frame().flip_x);
+        assert_eq!(tick(&mut animator, 0.2, &player), Walk);
+
+        player.movement.release_left();
+        assert_eq!(tick(&mut animator, 0.1, &player), Idle);
+
+        // rising behind a wall climbs, and falls once it stops
+        player.standing_on = None;
+        player.behind_wall = true;
+        player.z_vel = 5.0;
+        assert_eq!(tick(&mut animator, 0.1, &player), Climb);
+        player.z_vel = -1.0;
+        assert_eq!(tick(&mut animator, 0.1, &player), Fall);
+    }
+
+    #[test]
+    fn frame_timing() {
+        let mut animator = PlayerAnimator::new();
+        let mut player = Player::new((0.0, 0.0, 0.0));
+        player.standing_on = Some(0);
+        player.movement.press_right();
+        tick(&mut animator, 0.0, &player);
+        tick(&mut animator, 1.0, &player);
+        assert_eq!(animator.state, PlayerState::Walk);
+
+        // ten frames a second, wrapping around after four
+        let cell = |animator: &PlayerAnimator| animator.frame().cell;
+        assert_eq!(cell(&animator), (0, WALK.row));
+        tick(&mut animator, 0.25, &player);
+        assert_eq!(cell(&animator), (2, WALK.row));
+        tick(&mut animator, 0.2, &player);
+        assert_eq!(cell(&animator), (0, WALK.row));
+
+        // and rising stops on its last frame
+        player.standing_on = None;
+        player.z_vel = 5.0;
+        tick(&mut animator, 0.1, &player);
+        tick(&mut animator, 1.0, &player);
+        assert_eq!(cell(&animator), (JUMP_RISE.frames - 1, JUMP_RISE.row));
+    }
+}
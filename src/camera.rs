@@ -66,6 +66,16 @@ impl Camera {
         self.perspective
     }
 
+    pub fn state(&self) -> CameraState {
+        self.state
+    }
+
+    /// How far the camera still has to turn to face `perspective`, from -1
+    /// to 1 in quarter turns. Zero when stopped.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
     pub fn tick(&mut self, delta: f32) {
         match self.state {
             CameraState::Stopped => (),
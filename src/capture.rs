@@ -0,0 +1,138 @@
+//! Screenshots and short gif clips of the window, for bug reports.
+
+use crate::resources::Image;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where captures are written, relative to the working directory.
+pub const CAPTURE_DIR: &str = "captures";
+
+pub const CLIP_LENGTH: Duration = Duration::from_secs(3);
+// gif delays are in hundredths of a second
+const CLIP_FRAME_DELAY: u16 = 5;
+
+/// Reads the back buffer of the default framebuffer. Call after drawing,
+/// before swapping buffers.
+fn read_window(width: u32, height: u32) -> Image {
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl::ReadBuffer(gl::BACK);
+    }
+
+    Image::read_framebuffer(width, height)
+}
+
+/// Saves the window contents as a timestamped png, returning its path.
+pub fn screenshot(width: u32, height: u32) -> io::Result<PathBuf> {
+    let path = capture_path("screenshot", "png")?;
+    read_window(width, height).save_png(&path)?;
+
+    Ok(path)
+}
+
+/// Collects frames for `CLIP_LENGTH`, then writes them out as a looping gif.
+pub struct ClipRecorder {
+    frames: Vec<Image>,
+    started: Instant,
+    last_frame: Option<Instant>,
+}
+
+impl ClipRecorder {
+    pub fn new() -> ClipRecorder {
+        ClipRecorder {
+            frames: Vec::new(),
+            started: Instant::now(),
+            last_frame: None,
+        }
+    }
+
+    /// Captures the window if enough time has passed since the last frame.
+    /// Returns false once the clip is long enough.
+    pub fn capture(&mut self, width: u32, height: u32) -> bool {
+        let frame_interval = Duration::from_millis(10*CLIP_FRAME_DELAY as u64);
+        if self.last_frame.is_none_or(|last| last.elapsed() >= frame_interval) {
+            self.frames.push(read_window(width, height));
+            self.last_frame = Some(Instant::now());
+        }
+
+        self.started.elapsed() < CLIP_LENGTH
+    }
+
+    /// Encodes the clip on a background thread, since quantizing every frame
+    /// takes a while. Prints where it ended up once done.
+    pub fn finish(self) {
+        let frames = self.frames;
+
+        std::thread::spawn(move || {
+            let result = capture_path("clip", "gif")
+                .and_then(|path| encode_gif(&path, frames).map(|_| path));
+
+            match result {
+                Ok(path) => log::info!("saved clip to {}", path.display()),
+                Err(err) => log::error!("could not save clip: {}", err),
+            }
+        });
+    }
+}
+
+fn encode_gif(path: &Path, frames: Vec<Image>) -> io::Result<()> {
+    let (width, height) = match frames.first() {
+        Some(frame) => (frame.width as u16, frame.height as u16),
+        None => return Ok(()),
+    };
+
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(gif_error)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+
+    for mut image in frames {
+        // speed 10 is the crate's recommended tradeoff for quantization
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut image.pixels, 10);
+        frame.delay = CLIP_FRAME_DELAY;
+        encoder.write_frame(&frame).map_err(gif_error)?;
+    }
+
+    Ok(())
+}
+
+fn gif_error(err: gif::EncodingError) -> io::Error {
+    match err {
+        gif::EncodingError::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+/// `captures/<prefix>-<utc date>_<utc time>.<extension>`, creating the
+/// directory if needed.
+fn capture_path(prefix: &str, extension: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(CAPTURE_DIR)?;
+
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+
+    Ok(Path::new(CAPTURE_DIR).join(format!("{}-{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}.{}",
+        prefix, year, month, day,
+        time / 3600, time / 60 % 60, time % 60, since_epoch.subsec_millis(),
+        extension)))
+}
+
+// days since 1970-01-01 to a proleptic gregorian date, after Howard Hinnant's
+// `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2) / 153;
+    let day = (doy - (153*mp + 2)/5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era*400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,313 @@
+//! Immediate-mode debug drawing, callable from anywhere in gameplay code
+//! through the `debug_draw!` macro:
+//!
+//! ```ignore
+//! debug_draw!(arrow(from, to, (1.0, 0.0, 0.0)));
+//! debug_draw!(text(position, "landed", (1.0, 1.0, 1.0))).for_seconds(1.0);
+//! ```
+//!
+//! Shapes are queued per thread and drawn over everything else by `flush`,
+//! at the end of `Game::draw`. They last until that flush, or as long as
+//! asked for with `Shape::for_frames` or `Shape::for_seconds`.
+//!
+//! In release builds there is no queue, every function does nothing and
+//! the macro doesn't even evaluate its arguments. Shapes are only queued
+//! while debugging something, so most functions usually have no callers.
+
+use cgmath::Vector3;
+
+use crate::resources::Resources;
+
+type Color = (f32, f32, f32);
+
+/// A queued shape, for picking how long it's drawn. Does nothing once the
+/// shape has been flushed for the last time.
+pub struct Shape {
+    // 0 for no shape
+    #[cfg(debug_assertions)]
+    id: u64,
+}
+
+#[allow(dead_code)]
+impl Shape {
+    /// Stands in for a shape that wasn't queued.
+    pub fn none() -> Shape {
+        Shape {
+            #[cfg(debug_assertions)]
+            id: 0,
+        }
+    }
+
+    /// Keeps drawing the shape for `frames` flushes in all.
+    pub fn for_frames(self, frames: u32) {
+        #[cfg(debug_assertions)]
+        queue::set_lifetime(self.id, queue::Lifetime::Frames(frames));
+        #[cfg(not(debug_assertions))]
+        let _ = frames;
+    }
+
+    /// Keeps drawing the shape until `seconds` have passed since it was
+    /// queued, and at least once.
+    pub fn for_seconds(self, seconds: f32) {
+        #[cfg(debug_assertions)]
+        queue::set_lifetime(self.id, queue::Lifetime::Until(std::time::Instant::now() + std::time::Duration::from_secs_f32(seconds.max(0.0))));
+        #[cfg(not(debug_assertions))]
+        let _ = seconds;
+    }
+}
+
+/// Where shapes are drawn.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Space {
+    World,
+    /// Pixels from the top left corner of the viewport, with z ignored.
+    Screen,
+}
+
+// queues the shape built by `build`, which in release builds never runs
+#[inline(always)]
+fn push<F: FnOnce() -> (Space, Vec<(Vector3<f32>, Vector3<f32>)>, Option<(Vector3<f32>, String)>)>(color: Color, build: F) -> Shape {
+    #[cfg(debug_assertions)]
+    {
+        let (space, lines, label) = build();
+        Shape {
+            id: queue::push(space, color, lines, label),
+        }
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = (color, build);
+        Shape {}
+    }
+}
+
+#[allow(dead_code)]
+pub fn line(from: Vector3<f32>, to: Vector3<f32>, color: Color) -> Shape {
+    push(color, || (Space::World, vec![(from, to)], None))
+}
+
+/// A box along the axes, between the corners `min` and `max`.
+#[allow(dead_code)]
+pub fn aabb(min: Vector3<f32>, max: Vector3<f32>, color: Color) -> Shape {
+    push(color, || {
+        let corner = |i: usize| Vector3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        // corners one bit apart share an edge
+        let lines = (0..8)
+            .flat_map(|i| [1, 2, 4].iter().filter(move |&&bit| i & bit == 0).map(move |bit| (i, i | bit)))
+            .map(|(a, b)| (corner(a), corner(b)))
+            .collect();
+
+        (Space::World, lines, None)
+    })
+}
+
+/// Three circles around `center`, one across each axis.
+#[allow(dead_code)]
+pub fn sphere(center: Vector3<f32>, radius: f32, color: Color) -> Shape {
+    const SEGMENTS: usize = 24;
+
+    push(color, || {
+        let point = |axis: usize, i: usize| {
+            let (sin, cos) = (i as f32 / SEGMENTS as f32 * std::f32::consts::TAU).sin_cos();
+            center + radius*match axis {
+                0 => Vector3::new(0.0, cos, sin),
+                1 => Vector3::new(cos, 0.0, sin),
+                _ => Vector3::new(cos, sin, 0.0),
+            }
+        };
+        let lines = (0..3)
+            .flat_map(|axis| (0..SEGMENTS).map(move |i| (point(axis, i), point(axis, i + 1))))
+            .collect();
+
+        (Space::World, lines, None)
+    })
+}
+
+/// A line from `from` to `to` with a four sided head at `to`.
+#[allow(dead_code)]
+pub fn arrow(from: Vector3<f32>, to: Vector3<f32>, color: Color) -> Shape {
+    use cgmath::InnerSpace;
+
+    push(color, || {
+        // any two directions across the arrow
+        let along = to - from;
+        let other = if along.x.abs() < 0.9*along.magnitude() { Vector3::unit_x() } else { Vector3::unit_y() };
+        let side = along.cross(other);
+        let side2 = along.cross(side);
+
+        (Space::World, arrow_lines(from, to, &[side, -side, side2, -side2]), None)
+    })
+}
+
+/// The lines of an arrow from `from` to `to`, with a side of its head
+/// reaching towards each of `sides`, which must be across the arrow.
+pub fn arrow_lines(from: Vector3<f32>, to: Vector3<f32>, sides: &[Vector3<f32>]) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+    use cgmath::InnerSpace;
+
+    let mut lines = vec![(from, to)];
+
+    // a fixed size, unless the arrow is too short for it
+    let along = to - from;
+    let length = along.magnitude();
+    if length > 1e-4 {
+        let head = 0.2f32.min(0.5*length);
+        let back = to - along/length*head;
+        for side in sides {
+            lines.push((to, back + 0.5*head*side.normalize()));
+        }
+    }
+
+    lines
+}
+
+/// `text` with its top left corner where `position` is on screen.
+#[allow(dead_code)]
+pub fn text<S: Into<String>>(position: Vector3<f32>, text: S, color: Color) -> Shape {
+    push(color, || (Space::World, Vec::new(), Some((position, text.into()))))
+}
+
+/// Like `line`, in screen space.
+#[allow(dead_code)]
+pub fn screen_line(from: (f32, f32), to: (f32, f32), color: Color) -> Shape {
+    push(color, || (Space::Screen, vec![(Vector3::new(from.0, from.1, 0.0), Vector3::new(to.0, to.1, 0.0))], None))
+}
+
+/// A rectangle between the corners `min` and `max`, in screen space.
+#[allow(dead_code)]
+pub fn screen_rect(min: (f32, f32), max: (f32, f32), color: Color) -> Shape {
+    push(color, || {
+        let corners = [
+            Vector3::new(min.0, min.1, 0.0),
+            Vector3::new(max.0, min.1, 0.0),
+            Vector3::new(max.0, max.1, 0.0),
+            Vector3::new(min.0, max.1, 0.0),
+        ];
+        let lines = (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect();
+
+        (Space::Screen, lines, None)
+    })
+}
+
+/// Like `text`, in screen space.
+#[allow(dead_code)]
+pub fn screen_text<S: Into<String>>(position: (f32, f32), text: S, color: Color) -> Shape {
+    push(color, || (Space::Screen, Vec::new(), Some((Vector3::new(position.0, position.1, 0.0), text.into()))))
+}
+
+/// Draws everything queued, in one batch of lines and one of text, then
+/// forgets what has expired.
+pub fn flush(res: &Resources) {
+    #[cfg(debug_assertions)]
+    queue::flush(res);
+    #[cfg(not(debug_assertions))]
+    let _ = res;
+}
+
+/// Number of shapes waiting to be drawn.
+#[allow(dead_code)]
+pub fn queued() -> usize {
+    #[cfg(debug_assertions)]
+    return queue::len();
+    #[cfg(not(debug_assertions))]
+    0
+}
+
+#[cfg(debug_assertions)]
+mod queue {
+    use cgmath::Vector3;
+
+    use std::cell::{Cell, RefCell};
+    use std::time::Instant;
+
+    use super::{Color, Space};
+    use crate::resources::{Resources, TextStyle};
+
+    pub enum Lifetime {
+        // flushes left
+        Frames(u32),
+        Until(Instant),
+    }
+
+    struct Item {
+        // stays the same as items before it expire, unlike its index
+        id: u64,
+        space: Space,
+        color: Color,
+        lines: Vec<(Vector3<f32>, Vector3<f32>)>,
+        label: Option<(Vector3<f32>, String)>,
+        lifetime: Lifetime,
+    }
+
+    thread_local! {
+        static QUEUE: RefCell<Vec<Item>> = const { RefCell::new(Vec::new()) };
+        static LAST_ID: Cell<u64> = const { Cell::new(0) };
+    }
+
+    /// Queues a shape for one flush, returning its id.
+    pub fn push(space: Space, color: Color, lines: Vec<(Vector3<f32>, Vector3<f32>)>, label: Option<(Vector3<f32>, String)>) -> u64 {
+        let id = LAST_ID.with(|last_id| {
+            last_id.set(last_id.get() + 1);
+            last_id.get()
+        });
+        QUEUE.with(|queue| queue.borrow_mut().push(Item { id, space, color, lines, label, lifetime: Lifetime::Frames(1) }));
+
+        id
+    }
+
+    pub fn set_lifetime(id: u64, lifetime: Lifetime) {
+        QUEUE.with(|queue| {
+            // most likely just queued
+            if let Some(item) = queue.borrow_mut().iter_mut().rev().find(|item| item.id == id) {
+                item.lifetime = lifetime;
+            }
+        });
+    }
+
+    pub fn len() -> usize {
+        QUEUE.with(|queue| queue.borrow().len())
+    }
+
+    pub fn flush(res: &Resources) {
+        QUEUE.with(|queue| {
+            let mut queue = queue.borrow_mut();
+            let lines = res.lines();
+            let text = res.text();
+
+            for item in queue.iter() {
+                for &(from, to) in &item.lines {
+                    match item.space {
+                        Space::World => lines.line(from, to, item.color),
+                        Space::Screen => lines.screen_line((from.x, from.y), (to.x, to.y), item.color),
+                    }
+                }
+
+                if let Some((position, label)) = &item.label {
+                    let position = match item.space {
+                        Space::World => res.world_to_screen(*position),
+                        Space::Screen => (position.x, position.y),
+                    };
+                    text.queue(label, position, &TextStyle {
+                        scale: 1.0,
+                        color: (item.color.0, item.color.1, item.color.2, 1.0),
+                        ..TextStyle::default()
+                    });
+                }
+            }
+            lines.flush();
+            text.flush();
+
+            let now = Instant::now();
+            queue.retain_mut(|item| match &mut item.lifetime {
+                Lifetime::Frames(frames) => {
+                    *frames = frames.saturating_sub(1);
+                    *frames > 0
+                },
+                Lifetime::Until(until) => now < *until,
+            });
+        });
+    }
+}
@@ -0,0 +1,91 @@
+//! Collision shapes and player state drawn over the scene, toggled with
+//! `F4`.
+//!
+//! Every platform is outlined as the camera sees it, with its top widened by
+//! half the player's width: where `Player::tick` lands the player. The
+//! platform the player stands on is highlighted.
+
+use cgmath::Vector3;
+
+use crate::camera::{Camera, Perspective};
+use crate::debug_draw;
+use crate::level::Level;
+use crate::player::Player;
+use crate::resources::{Resources, TextStyle};
+
+const OUTLINE_COLOR: (f32, f32, f32) = (0.5, 0.5, 0.55);
+const LANDING_COLOR: (f32, f32, f32) = (0.3, 0.9, 0.4);
+const STANDING_ON_COLOR: (f32, f32, f32) = (1.0, 0.85, 0.2);
+const PLAYER_COLOR: (f32, f32, f32) = (0.3, 0.7, 1.0);
+const VELOCITY_COLOR: (f32, f32, f32) = (1.0, 0.35, 0.3);
+
+// seconds of movement the velocity arrow shows
+const VELOCITY_SCALE: f32 = 0.1;
+
+pub fn draw(res: &Resources, level: &Level, player: &Player, camera: &Camera) {
+    let lines = res.lines();
+
+    // the collision checks only look along this axis, and at z
+    let across = match camera.perspective() {
+        Perspective::S | Perspective::N => Vector3::unit_x(),
+        Perspective::W | Perspective::E => Vector3::unit_y(),
+    };
+    let up = Vector3::unit_z();
+
+    for (i, platform) in level.platforms.iter().enumerate() {
+        let top = Vector3::from(platform.surface_center);
+        let half_width = 0.5 * match camera.perspective() {
+            Perspective::S | Perspective::N => platform.surface_dim.0,
+            Perspective::W | Perspective::E => platform.surface_dim.1,
+        };
+        let bottom = top - platform.height*up;
+
+        let standing_on = player.standing_on == Some(i);
+        lines.outline(&[
+            top - half_width*across,
+            top + half_width*across,
+            bottom + half_width*across,
+            bottom - half_width*across,
+        ], if standing_on { STANDING_ON_COLOR } else { OUTLINE_COLOR });
+
+        // intersection_x and intersection_y count the player's half width
+        let reach = (half_width + 0.5)*across;
+        lines.line(top - reach, top + reach, if standing_on { STANDING_ON_COLOR } else { LANDING_COLOR });
+    }
+
+    let feet = Vector3::from(player.pos);
+    lines.outline(&[
+        feet - 0.5*across,
+        feet + 0.5*across,
+        feet + 0.5*across + up,
+        feet - 0.5*across + up,
+    ], PLAYER_COLOR);
+
+    let center = feet + 0.5*up;
+    let velocity = VELOCITY_SCALE*Vector3::from(player.velocity(camera.perspective()));
+    arrow(res, center, center + velocity, across, VELOCITY_COLOR);
+
+    let text = res.text();
+    text.queue(&format!(
+        "movement: {:?}\nz_vel: {:.2}\nstanding_on: {:?}\nbehind_wall: {}\ncamera: {:?} {:?} phase {:.2}",
+        player.movement,
+        player.z_vel,
+        player.standing_on,
+        player.behind_wall,
+        camera.perspective(),
+        camera.state(),
+        camera.phase(),
+    ), (8.0, 8.0), &TextStyle {
+        max_width: Some(text.screen_size().0 - 16.0),
+        ..TextStyle::default()
+    });
+}
+
+// a line with a head in the plane of `across` and z
+fn arrow(res: &Resources, from: Vector3<f32>, to: Vector3<f32>, across: Vector3<f32>, color: (f32, f32, f32)) {
+    let lines = res.lines();
+    let normal = (to - from).cross(Vector3::unit_z().cross(across));
+    for (from, to) in debug_draw::arrow_lines(from, to, &[normal, -normal]) {
+        lines.line(from, to, color);
+    }
+}
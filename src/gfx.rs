@@ -1,8 +1,19 @@
 //! RAII-enabled OpenGL object containers.
 
+mod error;
+pub use error::*;
+
+pub mod debug;
+
 mod shader;
 pub use shader::*;
 
+mod preprocess;
+pub use preprocess::*;
+
+mod uniform;
+pub use uniform::*;
+
 mod vao;
 pub use vao::*;
 
@@ -11,3 +22,9 @@ pub use bo::*;
 
 mod texture;
 pub use texture::*;
+
+mod framebuffer;
+pub use framebuffer::*;
+
+mod mesh;
+pub use mesh::*;
@@ -1,5 +1,9 @@
 use gl::types::*;
 
+use std::marker::PhantomData;
+use std::mem::{size_of, size_of_val};
+use std::ops::{Deref, DerefMut, Range};
+
 #[derive(Debug)]
 pub struct BufferObject(GLuint);
 
@@ -17,6 +21,12 @@ impl BufferObject {
     }
 }
 
+impl Default for BufferObject {
+    fn default() -> BufferObject {
+        BufferObject::new()
+    }
+}
+
 impl Drop for BufferObject {
     fn drop(&mut self) {
         unsafe {
@@ -24,3 +34,206 @@ impl Drop for BufferObject {
         }
     }
 }
+
+/// What a buffer is bound as when drawing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BufferTarget {
+    /// Vertex attributes, per vertex or per instance.
+    Array,
+    /// Indices for `DrawElements`.
+    ElementArray,
+}
+
+impl BufferTarget {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            BufferTarget::Array => gl::ARRAY_BUFFER,
+            BufferTarget::ElementArray => gl::ELEMENT_ARRAY_BUFFER,
+        }
+    }
+}
+
+/// How often the contents of a buffer are expected to change.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BufferUsage {
+    /// Set once, drawn many times.
+    Static,
+    /// Changed every now and then.
+    Dynamic,
+    /// Rewritten about every frame.
+    Stream,
+}
+
+impl BufferUsage {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+/// A buffer holding `len` values of type `T`.
+///
+/// Uploads go through `COPY_WRITE_BUFFER`, so they never disturb the
+/// element buffer of whatever vao happens to be bound.
+pub struct Buffer<T: Copy> {
+    object: BufferObject,
+    target: BufferTarget,
+    usage: BufferUsage,
+    len: usize,
+    _type: PhantomData<T>,
+}
+
+impl<T: Copy> Buffer<T> {
+    /// Creates an empty buffer.
+    pub fn new(target: BufferTarget, usage: BufferUsage) -> Buffer<T> {
+        Buffer {
+            object: BufferObject::new(),
+            target,
+            usage,
+            len: 0,
+            _type: PhantomData,
+        }
+    }
+
+    pub fn from_data(target: BufferTarget, usage: BufferUsage, data: &[T]) -> Buffer<T> {
+        let mut buffer = Self::new(target, usage);
+        buffer.set_data(data);
+        buffer
+    }
+
+    /// Binds the buffer to its target.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target.to_gl(), self.object.name());
+        }
+    }
+
+    pub fn target(&self) -> BufferTarget {
+        self.target
+    }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn name(&self) -> GLuint {
+        self.object.name()
+    }
+
+    /// See `BufferObject::set_label`.
+    pub fn set_label(&self, label: &str) {
+        self.object.set_label(label);
+    }
+
+    /// Replaces the whole contents, reallocating the storage.
+    pub fn set_data(&mut self, data: &[T]) {
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.object.name());
+            gl::BufferData(gl::COPY_WRITE_BUFFER, size_of_val(data) as GLsizeiptr, data.as_ptr() as *const _, self.usage.to_gl());
+        }
+        self.len = data.len();
+    }
+
+    /// Overwrites the values starting at index `offset`.
+    pub fn update(&self, offset: usize, data: &[T]) {
+        assert!(offset + data.len() <= self.len, "buffer update out of range");
+
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.object.name());
+            gl::BufferSubData(gl::COPY_WRITE_BUFFER, (offset*size_of::<T>()) as GLintptr, size_of_val(data) as GLsizeiptr, data.as_ptr() as *const _);
+        }
+    }
+
+    /// Maps values `range` into memory, for reading and writing. They are
+    /// written back when the returned guard is dropped.
+    pub fn map(&mut self, range: Range<usize>) -> MappedBuffer<'_, T> {
+        assert!(range.start <= range.end && range.end <= self.len, "buffer map out of range");
+
+        let ptr = if range.is_empty() {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            unsafe {
+                gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.object.name());
+                gl::MapBufferRange(gl::COPY_WRITE_BUFFER,
+                    (range.start*size_of::<T>()) as GLintptr, (range.len()*size_of::<T>()) as GLsizeiptr,
+                    gl::MAP_READ_BIT | gl::MAP_WRITE_BIT) as *mut T
+            }
+        };
+        assert!(!ptr.is_null(), "could not map buffer");
+
+        MappedBuffer {
+            buffer: self,
+            ptr,
+            len: range.len(),
+        }
+    }
+}
+
+/// Values of a `Buffer` mapped into memory. Unmaps on drop.
+pub struct MappedBuffer<'a, T: Copy> {
+    buffer: &'a mut Buffer<T>,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<T: Copy> Deref for MappedBuffer<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe {
+            std::slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+}
+
+impl<T: Copy> DerefMut for MappedBuffer<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.ptr, self.len)
+        }
+    }
+}
+
+impl<T: Copy> Drop for MappedBuffer<'_, T> {
+    fn drop(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.buffer.object.name());
+            // the contents are undefined if the storage was lost while mapped
+            if gl::UnmapBuffer(gl::COPY_WRITE_BUFFER) == gl::FALSE {
+                log::error!("buffer {} was corrupted while mapped", self.buffer.object.name());
+            }
+        }
+    }
+}
+
+/// Types usable as indices in an element buffer.
+pub trait Index: Copy {
+    const GL_TYPE: GLenum;
+}
+
+impl Index for u8 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_BYTE;
+}
+
+impl Index for u16 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_SHORT;
+}
+
+impl Index for u32 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_INT;
+}
@@ -0,0 +1,146 @@
+//! `KHR_debug` support: driver messages routed into the `log` crate, object
+//! labels and debug groups, which show up in tools like RenderDoc.
+//!
+//! Everything here is a no-op until `enable` succeeds, which needs a debug
+//! context.
+
+use gl::types::*;
+
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::*;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the message callback. Returns false if the driver doesn't
+/// support `KHR_debug`.
+pub fn enable() -> bool {
+    if !gl::DebugMessageCallback::is_loaded() {
+        return false;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // report errors from the offending call, so backtraces are useful
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(message_callback), std::ptr::null());
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+
+    true
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+extern "system" fn message_callback(
+    source: GLenum,
+    type_: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut std::ffi::c_void,
+) {
+    // not always nul-terminated, e.g. for debug groups pushed by length
+    let message = unsafe {
+        if length < 0 {
+            CStr::from_ptr(message).to_bytes()
+        } else {
+            std::slice::from_raw_parts(message as *const u8, length as usize)
+        }
+    };
+    let message = String::from_utf8_lossy(message);
+
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => log::Level::Info,
+        // notifications come every frame on some drivers
+        _ => log::Level::Trace,
+    };
+
+    log::log!(target: "gl", level, "[{} {} {}] {}", source_name(source), type_name(type_), id, message.trim_end());
+}
+
+fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(type_: GLenum) -> &'static str {
+    match type_ {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+fn object_label(identifier: GLenum, name: GLuint, label: &str) {
+    if !is_enabled() {
+        return;
+    }
+
+    unsafe {
+        gl::ObjectLabel(identifier, name, label.len() as GLsizei, label.as_ptr() as *const GLchar);
+    }
+}
+
+macro_rules! impl_set_label {
+    ($($type:ty => $identifier:expr),* $(,)?) => {
+        $(impl $type {
+            /// Names the object in debug messages and tools, in debug mode.
+            pub fn set_label(&self, label: &str) {
+                object_label($identifier, self.name(), label);
+            }
+        })*
+    };
+}
+
+impl_set_label! {
+    BufferObject => gl::BUFFER,
+    VertexArrayObject => gl::VERTEX_ARRAY,
+    Shader => gl::SHADER,
+    ShaderProgram => gl::PROGRAM,
+    Texture => gl::TEXTURE,
+    Framebuffer => gl::FRAMEBUFFER,
+    Renderbuffer => gl::RENDERBUFFER,
+}
+
+/// Marks the commands issued until it is dropped, as returned by `group`.
+pub struct DebugGroup(());
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if is_enabled() {
+            unsafe {
+                gl::PopDebugGroup();
+            }
+        }
+    }
+}
+
+/// Pushes a debug group named `name`, popped when the result is dropped.
+pub fn group(name: &str) -> DebugGroup {
+    if is_enabled() {
+        unsafe {
+            gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, name.len() as GLsizei, name.as_ptr() as *const GLchar);
+        }
+    }
+
+    DebugGroup(())
+}
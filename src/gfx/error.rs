@@ -0,0 +1,147 @@
+use gl::types::*;
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use super::{AttribType, IncompleteFramebuffer, ShaderType};
+
+/// Anything that can go wrong setting up or using OpenGL objects.
+#[derive(Debug)]
+pub enum Error {
+    /// A shader failed to compile. `source` names the file (or constant)
+    /// the code came from.
+    Compile {
+        source: String,
+        stage: ShaderType,
+        log: String,
+    },
+    /// A shader couldn't be preprocessed, because of something at `line`
+    /// of the file `source`.
+    Preprocess {
+        source: String,
+        line: usize,
+        message: String,
+    },
+    /// A program failed to link. `program` names what it was built for.
+    Link {
+        program: String,
+        log: String,
+    },
+    /// A vertex layout refers to an input the program doesn't have, or
+    /// doesn't use.
+    MissingAttribute(String),
+    /// A vertex layout and the program disagree on the type of an input.
+    /// `shader` is the GL type enum, as it may not be a float vector.
+    MismatchedAttribute {
+        name: String,
+        layout: AttribType,
+        shader: GLenum,
+    },
+    /// A required uniform is missing, or was optimised out.
+    MissingUniform(String),
+    /// An image file couldn't be read or decoded.
+    ImageDecode {
+        path: Option<PathBuf>,
+        error: io::Error,
+    },
+    /// An image decoded fine, but can't be used as a texture.
+    UnsupportedFormat {
+        path: Option<PathBuf>,
+        format: String,
+    },
+    /// A bitmap font couldn't be parsed. `source` names the file it came
+    /// from.
+    FontParse {
+        source: String,
+        message: String,
+    },
+    /// Reading or writing a file failed, or the data in it was invalid.
+    Io {
+        path: Option<PathBuf>,
+        error: io::Error,
+    },
+    /// A shader source file couldn't be read.
+    ReadSource {
+        path: PathBuf,
+        error: io::Error,
+    },
+    FramebufferIncomplete(IncompleteFramebuffer),
+    /// `glGetError` reported `code` after `context`.
+    Gl {
+        code: GLenum,
+        context: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Compile { source, stage, log } => write!(f, "could not compile {} shader {}:\n{}", stage, source, log.trim_end()),
+            Error::Preprocess { source, line, message } => write!(f, "{}:{}: {}", source, line, message),
+            Error::Link { program, log } => write!(f, "could not link {} program:\n{}", program, log.trim_end()),
+            Error::MissingAttribute(name) => write!(f, "the shader has no active attribute `{}`", name),
+            Error::MismatchedAttribute { name, layout, shader } => match AttribType::from_gl(*shader) {
+                Some(shader) => write!(f, "attribute `{}` is a {} in the vertex layout but a {} in the shader", name, layout, shader),
+                None => write!(f, "attribute `{}` is a {} in the vertex layout but of type 0x{:x} in the shader", name, layout, shader),
+            },
+            Error::MissingUniform(name) => write!(f, "the shader has no active uniform `{}`", name),
+            Error::ImageDecode { path: Some(path), error } => write!(f, "could not decode {}: {}", path.display(), error),
+            Error::ImageDecode { path: None, error } => write!(f, "could not decode image: {}", error),
+            Error::UnsupportedFormat { path: Some(path), format } => write!(f, "{} has an unsupported format: {}", path.display(), format),
+            Error::UnsupportedFormat { path: None, format } => write!(f, "unsupported image format: {}", format),
+            Error::FontParse { source, message } => write!(f, "could not parse font {}: {}", source, message),
+            Error::Io { path: Some(path), error } => write!(f, "{}: {}", path.display(), error),
+            Error::Io { path: None, error } => error.fmt(f),
+            Error::ReadSource { path, error } => write!(f, "could not read {}: {}", path.display(), error),
+            Error::FramebufferIncomplete(err) => err.fmt(f),
+            Error::Gl { code, context } => write!(f, "opengl error {} (0x{:x}) {}", gl_error_name(*code), code, context),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ImageDecode { error, .. } | Error::Io { error, .. } | Error::ReadSource { error, .. } => Some(error),
+            Error::FramebufferIncomplete(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<IncompleteFramebuffer> for Error {
+    fn from(err: IncompleteFramebuffer) -> Error {
+        Error::FramebufferIncomplete(err)
+    }
+}
+
+/// Turns the oldest pending `glGetError` into an `Error`, describing where
+/// it was noticed with `context`. Other pending errors are discarded.
+pub fn check_gl_error(context: &str) -> Result<(), Error> {
+    let code = unsafe {
+        gl::GetError()
+    };
+    if code == gl::NO_ERROR {
+        return Ok(());
+    }
+
+    // each call only returns one flag
+    while unsafe { gl::GetError() } != gl::NO_ERROR {}
+
+    Err(Error::Gl {
+        code,
+        context: context.to_string(),
+    })
+}
+
+fn gl_error_name(code: GLenum) -> &'static str {
+    match code {
+        gl::INVALID_ENUM => "INVALID_ENUM",
+        gl::INVALID_VALUE => "INVALID_VALUE",
+        gl::INVALID_OPERATION => "INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "OUT_OF_MEMORY",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,411 @@
+use gl::types::*;
+use std::fmt;
+
+use super::Texture;
+
+/// Returned when a framebuffer fails its completeness check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IncompleteFramebuffer(pub GLenum);
+
+impl fmt::Display for IncompleteFramebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.0 {
+            gl::FRAMEBUFFER_UNDEFINED => "undefined",
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "incomplete attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "missing attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "incomplete draw buffer",
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "incomplete read buffer",
+            gl::FRAMEBUFFER_UNSUPPORTED => "unsupported combination of formats",
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "mismatched multisample settings",
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "incomplete layer targets",
+            _ => "unknown status",
+        };
+
+        write!(f, "framebuffer incomplete: {} (0x{:x})", reason, self.0)
+    }
+}
+
+impl std::error::Error for IncompleteFramebuffer {}
+
+/// The draw and read framebuffer bindings, put back when dropped. Lets
+/// constructors bind what they set up without leaking it to the caller.
+struct SavedBindings {
+    draw: GLint,
+    read: GLint,
+}
+
+impl SavedBindings {
+    fn save() -> SavedBindings {
+        let mut bindings = SavedBindings { draw: 0, read: 0 };
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut bindings.draw);
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut bindings.read);
+        }
+        bindings
+    }
+}
+
+impl Drop for SavedBindings {
+    fn drop(&mut self) {
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw as GLuint);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.read as GLuint);
+        }
+    }
+}
+
+pub struct Framebuffer(GLuint);
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer(unsafe {
+            let mut name = 0;
+            gl::GenFramebuffers(1, &mut name as *mut GLuint);
+            name
+        })
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.0)
+        }
+    }
+
+    /// Binds the window's framebuffer.
+    pub fn bind_default() {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0)
+        }
+    }
+
+    /// Attaches `renderbuffer` to the framebuffer, which must be bound.
+    pub fn attach_renderbuffer(&self, attachment: GLenum, renderbuffer: &Renderbuffer) {
+        unsafe {
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, renderbuffer.name());
+        }
+    }
+
+    /// Attaches the base level of `texture` to the framebuffer, which must
+    /// be bound.
+    pub fn attach_texture(&self, attachment: GLenum, texture: &Texture) {
+        unsafe {
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture.name(), 0);
+        }
+    }
+
+    /// Checks the framebuffer, which must be bound, for completeness.
+    pub fn check_status(&self) -> Result<(), IncompleteFramebuffer> {
+        let status = unsafe {
+            gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+        };
+
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(IncompleteFramebuffer(status))
+        }
+    }
+
+    pub fn name(&self) -> GLuint {
+        self.0
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer::new()
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.0 as *const GLuint);
+        }
+    }
+}
+
+pub struct Renderbuffer(GLuint);
+
+impl Renderbuffer {
+    /// Allocates storage for a `width` by `height` renderbuffer.
+    pub fn new(internal_format: GLenum, width: u32, height: u32) -> Renderbuffer {
+        Self::new_multisample(internal_format, 0, width, height)
+    }
+
+    /// Like `new`, with `samples` samples per pixel. Zero means a regular,
+    /// single-sampled renderbuffer.
+    pub fn new_multisample(internal_format: GLenum, samples: u32, width: u32, height: u32) -> Renderbuffer {
+        let renderbuffer = Renderbuffer(unsafe {
+            let mut name = 0;
+            gl::GenRenderbuffers(1, &mut name as *mut GLuint);
+            name
+        });
+
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer.0);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples as GLsizei, internal_format, width as GLsizei, height as GLsizei);
+        }
+
+        renderbuffer
+    }
+
+    pub fn name(&self) -> GLuint {
+        self.0
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.0 as *const GLuint);
+        }
+    }
+}
+
+/// A framebuffer with nothing but a 24-bit depth texture, such as a shadow
+/// map. The texture is set up for `sampler2DShadow`, which compares against
+/// it with linear filtering. Lookups outside of it pass.
+pub struct DepthTarget {
+    framebuffer: Framebuffer,
+    depth: Texture,
+
+    width: u32,
+    height: u32,
+}
+
+impl DepthTarget {
+    /// Creates a `width` by `height` target. The framebuffer bindings are
+    /// left as they were.
+    pub fn new(width: u32, height: u32) -> Result<DepthTarget, IncompleteFramebuffer> {
+        let _bindings = SavedBindings::save();
+        let framebuffer = Framebuffer::new();
+        framebuffer.bind();
+
+        let depth = Texture::new_empty(gl::DEPTH_COMPONENT24, width, height);
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as GLint);
+            gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, [1.0, 1.0, 1.0, 1.0].as_ptr());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as GLint);
+        }
+        framebuffer.attach_texture(gl::DEPTH_ATTACHMENT, &depth);
+        unsafe {
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+        }
+
+        framebuffer.check_status()?;
+
+        Ok(DepthTarget {
+            framebuffer,
+            depth,
+
+            width,
+            height,
+        })
+    }
+
+    /// Labels the framebuffer and its texture in debug mode.
+    pub fn set_label(&self, label: &str) {
+        self.framebuffer.set_label(label);
+        self.depth.set_label(&format!("{} depth", label));
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Binds the target for drawing and covers it with the viewport.
+    pub fn bind(&self) {
+        self.framebuffer.bind();
+        unsafe {
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+
+    pub fn depth_texture(&self) -> &Texture {
+        &self.depth
+    }
+}
+
+enum ColorAttachment {
+    Texture(Texture),
+    // multisampled, resolved into `RenderTarget::resolved` by `resolve`
+    Renderbuffer(Renderbuffer),
+}
+
+/// A framebuffer together with the attachments it draws into: an RGBA8
+/// color buffer and, optionally, a 24-bit depth buffer.
+///
+/// Single-sampled targets render straight into a texture. Multisampled ones
+/// render into renderbuffers, and `resolve` copies them into a texture.
+pub struct RenderTarget {
+    framebuffer: Framebuffer,
+    color: ColorAttachment,
+    depth: Option<Renderbuffer>,
+    resolved: Option<Box<RenderTarget>>,
+
+    width: u32,
+    height: u32,
+}
+
+impl RenderTarget {
+    /// Creates a `width` by `height` target. `samples` above one enables
+    /// multisampling. The framebuffer bindings are left as they were.
+    pub fn new(width: u32, height: u32, samples: u32, depth: bool) -> Result<RenderTarget, IncompleteFramebuffer> {
+        let _bindings = SavedBindings::save();
+        let framebuffer = Framebuffer::new();
+        framebuffer.bind();
+
+        let color = if samples > 1 {
+            let color = Renderbuffer::new_multisample(gl::RGBA8, samples, width, height);
+            framebuffer.attach_renderbuffer(gl::COLOR_ATTACHMENT0, &color);
+            ColorAttachment::Renderbuffer(color)
+        } else {
+            let color = Texture::new_empty(gl::RGBA8, width, height);
+            unsafe {
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            }
+            framebuffer.attach_texture(gl::COLOR_ATTACHMENT0, &color);
+            ColorAttachment::Texture(color)
+        };
+
+        let depth = if depth {
+            let depth_samples = if samples > 1 { samples } else { 0 };
+            let depth = Renderbuffer::new_multisample(gl::DEPTH_COMPONENT24, depth_samples, width, height);
+            framebuffer.attach_renderbuffer(gl::DEPTH_ATTACHMENT, &depth);
+            Some(depth)
+        } else {
+            None
+        };
+
+        framebuffer.check_status()?;
+
+        let resolved = if samples > 1 {
+            Some(Box::new(Self::new(width, height, 1, false)?))
+        } else {
+            None
+        };
+
+        Ok(RenderTarget {
+            framebuffer,
+            color,
+            depth,
+            resolved,
+
+            width,
+            height,
+        })
+    }
+
+    /// Labels the framebuffer and its attachments in debug mode.
+    pub fn set_label(&self, label: &str) {
+        self.framebuffer.set_label(label);
+        match &self.color {
+            ColorAttachment::Texture(texture) => texture.set_label(&format!("{} color", label)),
+            ColorAttachment::Renderbuffer(renderbuffer) => renderbuffer.set_label(&format!("{} color", label)),
+        }
+        if let Some(depth) = &self.depth {
+            depth.set_label(&format!("{} depth", label));
+        }
+        if let Some(resolved) = &self.resolved {
+            resolved.set_label(&format!("{} resolved", label));
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Binds the target for drawing and covers it with the viewport.
+    pub fn bind(&self) {
+        self.framebuffer.bind();
+        unsafe {
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+
+    /// Binds the window's framebuffer, with a `width` by `height` viewport.
+    pub fn bind_default(width: u32, height: u32) {
+        Framebuffer::bind_default();
+        unsafe {
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+    }
+
+    /// Copies multisampled color into the texture returned by
+    /// `color_texture`, leaving the framebuffer bindings as they were. Does
+    /// nothing for single-sampled targets.
+    pub fn resolve(&self) {
+        if let Some(resolved) = &self.resolved {
+            let _bindings = SavedBindings::save();
+            self.blit(resolved.framebuffer.name(), resolved.width, resolved.height, gl::NEAREST);
+        }
+    }
+
+    /// The color buffer as a texture. For multisampled targets it holds what
+    /// was drawn as of the last `resolve`.
+    pub fn color_texture(&self) -> &Texture {
+        match (&self.color, &self.resolved) {
+            (ColorAttachment::Texture(texture), _) => texture,
+            (ColorAttachment::Renderbuffer(_), Some(resolved)) => resolved.color_texture(),
+            (ColorAttachment::Renderbuffer(_), None) => unreachable!("multisampled targets always have a resolve target"),
+        }
+    }
+
+    /// Binds the target as the read framebuffer. Multisampled targets bind
+    /// what was last resolved, see `resolve`.
+    pub fn bind_read(&self) {
+        let framebuffer = match &self.resolved {
+            Some(resolved) => &resolved.framebuffer,
+            None => &self.framebuffer,
+        };
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer.name());
+        }
+    }
+
+    /// Copies the color buffer onto the whole of the window's `width` by
+    /// `height` framebuffer, resolving multisampling on the way. Leaves the
+    /// window's framebuffer bound.
+    pub fn blit_to_default(&self, width: u32, height: u32) {
+        // scaling multisampled buffers isn't allowed, so resolve first
+        let source = match &self.resolved {
+            Some(resolved) if (width, height) != (self.width, self.height) => {
+                self.resolve();
+                resolved
+            },
+            _ => self,
+        };
+
+        source.blit(0, width, height, gl::NEAREST);
+        Framebuffer::bind_default();
+    }
+
+    fn blit(&self, target: GLuint, width: u32, height: u32, filter: GLenum) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer.name());
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target);
+            gl::BlitFramebuffer(
+                0, 0, self.width as GLint, self.height as GLint,
+                0, 0, width as GLint, height as GLint,
+                gl::COLOR_BUFFER_BIT, filter);
+        }
+    }
+}
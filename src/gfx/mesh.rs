@@ -0,0 +1,247 @@
+use gl::types::*;
+
+use std::ffi::CString;
+use std::fmt;
+
+use super::{Buffer, BufferTarget, BufferUsage, Error, Index, ShaderProgram, VertexArrayObject};
+
+/// GLSL type of a float vertex attribute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AttribType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+}
+
+impl AttribType {
+    pub fn components(self) -> GLint {
+        match self {
+            AttribType::Float => 1,
+            AttribType::Vec2 => 2,
+            AttribType::Vec3 => 3,
+            AttribType::Vec4 => 4,
+        }
+    }
+
+    pub(super) fn from_gl(type_: GLenum) -> Option<AttribType> {
+        match type_ {
+            gl::FLOAT => Some(AttribType::Float),
+            gl::FLOAT_VEC2 => Some(AttribType::Vec2),
+            gl::FLOAT_VEC3 => Some(AttribType::Vec3),
+            gl::FLOAT_VEC4 => Some(AttribType::Vec4),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AttribType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AttribType::Float => "float",
+            AttribType::Vec2 => "vec2",
+            AttribType::Vec3 => "vec3",
+            AttribType::Vec4 => "vec4",
+        })
+    }
+}
+
+/// Rust types usable as vertex struct fields.
+pub trait AttribValue {
+    const TYPE: AttribType;
+}
+
+impl AttribValue for f32 {
+    const TYPE: AttribType = AttribType::Float;
+}
+
+impl AttribValue for [f32; 2] {
+    const TYPE: AttribType = AttribType::Vec2;
+}
+
+impl AttribValue for [f32; 3] {
+    const TYPE: AttribType = AttribType::Vec3;
+}
+
+impl AttribValue for [f32; 4] {
+    const TYPE: AttribType = AttribType::Vec4;
+}
+
+/// One field of a vertex struct, and the shader input it feeds.
+#[derive(Clone, Copy, Debug)]
+pub struct VertexAttrib {
+    pub name: &'static str,
+    pub type_: AttribType,
+    pub offset: usize,
+}
+
+/// Describes how a `#[repr(C)]` vertex struct maps onto shader inputs.
+/// Usually implemented through the `vertex_layout!` macro.
+pub trait VertexLayout: Copy {
+    const ATTRIBS: &'static [VertexAttrib];
+}
+
+/// Vertex and, optionally, index buffers, together with a vao describing
+/// them to one program.
+pub struct Mesh<V: VertexLayout, I: Index = u16> {
+    vertices: Buffer<V>,
+    indices: Option<Buffer<I>>,
+    vao: VertexArrayObject,
+
+    mode: GLenum,
+}
+
+impl<V: VertexLayout> Mesh<V> {
+    /// Uploads `vertices`, drawn in order as `mode` primitives, and points
+    /// the matching attributes of `program` at them.
+    pub fn new(program: &ShaderProgram, mode: GLenum, vertices: &[V]) -> Result<Mesh<V>, Error> {
+        Self::build(program, mode, vertices, None)
+    }
+}
+
+impl<V: VertexLayout, I: Index> Mesh<V, I> {
+    /// Like `new`, but draws the vertices in the order given by `indices`.
+    pub fn indexed(program: &ShaderProgram, mode: GLenum, vertices: &[V], indices: &[I]) -> Result<Mesh<V, I>, Error> {
+        Self::build(program, mode, vertices, Some(indices))
+    }
+
+    fn build(program: &ShaderProgram, mode: GLenum, vertices: &[V], indices: Option<&[I]>) -> Result<Mesh<V, I>, Error> {
+        let locations = V::ATTRIBS.iter()
+            .map(|attrib| active_attrib(program, attrib))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let vertices = Buffer::from_data(BufferTarget::Array, BufferUsage::Static, vertices);
+        let indices = indices.map(|indices| Buffer::from_data(BufferTarget::ElementArray, BufferUsage::Static, indices));
+
+        let vao = VertexArrayObject::new();
+        vao.bind();
+        vertices.bind();
+        for (attrib, location) in V::ATTRIBS.iter().zip(locations) {
+            unsafe {
+                gl::VertexAttribPointer(location, attrib.type_.components(), gl::FLOAT, gl::FALSE, std::mem::size_of::<V>() as GLint, attrib.offset as *const _);
+                gl::EnableVertexAttribArray(location);
+            }
+        }
+        // the element buffer binding is part of the vao
+        if let Some(indices) = &indices {
+            indices.bind();
+        }
+
+        Ok(Mesh {
+            vertices,
+            indices,
+            vao,
+
+            mode,
+        })
+    }
+
+    /// Labels the vao and buffers in debug mode.
+    pub fn set_label(&self, label: &str) {
+        self.vao.set_label(label);
+        self.vertices.set_label(&format!("{} vertices", label));
+        if let Some(indices) = &self.indices {
+            indices.set_label(&format!("{} indices", label));
+        }
+    }
+
+    /// Binds the vao, e.g. to add per-instance attributes to it.
+    pub fn bind(&self) {
+        self.vao.bind();
+    }
+
+    /// Points the matching attributes of `program` at `instances`, advancing
+    /// once per instance in `draw_instanced` rather than once per vertex.
+    pub fn set_instances<T: VertexLayout>(&self, program: &ShaderProgram, instances: &Buffer<T>) -> Result<(), Error> {
+        let locations = T::ATTRIBS.iter()
+            .map(|attrib| active_attrib(program, attrib))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.vao.bind();
+        instances.bind();
+        for (attrib, location) in T::ATTRIBS.iter().zip(locations) {
+            unsafe {
+                gl::VertexAttribPointer(location, attrib.type_.components(), gl::FLOAT, gl::FALSE, std::mem::size_of::<T>() as GLint, attrib.offset as *const _);
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// For updating vertices in place. Changing the vertex count of an
+    /// indexed mesh is fine, as long as the indices stay in range.
+    pub fn vertices_mut(&mut self) -> &mut Buffer<V> {
+        &mut self.vertices
+    }
+
+    pub fn indices_mut(&mut self) -> Option<&mut Buffer<I>> {
+        self.indices.as_mut()
+    }
+
+    /// Number of vertices a draw call processes: the index count of indexed
+    /// meshes.
+    pub fn count(&self) -> usize {
+        match &self.indices {
+            Some(indices) => indices.len(),
+            None => self.vertices.len(),
+        }
+    }
+
+    pub fn draw(&self) {
+        self.vao.bind();
+        unsafe {
+            match &self.indices {
+                Some(indices) => gl::DrawElements(self.mode, indices.len() as GLsizei, I::GL_TYPE, std::ptr::null()),
+                None => gl::DrawArrays(self.mode, 0, self.vertices.len() as GLsizei),
+            }
+        }
+    }
+
+    pub fn draw_instanced(&self, instances: usize) {
+        self.vao.bind();
+        unsafe {
+            match &self.indices {
+                Some(indices) => gl::DrawElementsInstanced(self.mode, indices.len() as GLsizei, I::GL_TYPE, std::ptr::null(), instances as GLsizei),
+                None => gl::DrawArraysInstanced(self.mode, 0, self.vertices.len() as GLsizei, instances as GLsizei),
+            }
+        }
+    }
+}
+
+// location of `attrib` in `program`, checking its type
+fn active_attrib(program: &ShaderProgram, attrib: &VertexAttrib) -> Result<GLuint, Error> {
+    let name = CString::new(attrib.name).expect("attribute names can't contain nul bytes");
+    let location = unsafe {
+        gl::GetAttribLocation(program.name(), name.as_ptr())
+    };
+    if location == -1 {
+        return Err(Error::MissingAttribute(attrib.name.to_string()));
+    }
+
+    let mut count = 0;
+    unsafe {
+        gl::GetProgramiv(program.name(), gl::ACTIVE_ATTRIBUTES, &mut count);
+    }
+    for i in 0..count as GLuint {
+        let mut buf = [0u8; 256];
+        let (mut len, mut size, mut type_) = (0, 0, 0);
+        unsafe {
+            gl::GetActiveAttrib(program.name(), i, buf.len() as GLsizei, &mut len, &mut size, &mut type_, buf.as_mut_ptr() as *mut GLchar);
+        }
+
+        if &buf[..len as usize] == attrib.name.as_bytes() {
+            if AttribType::from_gl(type_) != Some(attrib.type_) {
+                return Err(Error::MismatchedAttribute {
+                    name: attrib.name.to_string(),
+                    layout: attrib.type_,
+                    shader: type_,
+                });
+            }
+            break;
+        }
+    }
+
+    Ok(location as GLuint)
+}
@@ -0,0 +1,154 @@
+//! A small preprocessing step run before `Shader::compile`: `#include`s,
+//! injected `#define`s and `#line` directives. Conditionals and macros are
+//! left to the GLSL preprocessor.
+
+use std::borrow::Cow;
+
+use super::Error;
+
+// line numbers of the nth file start at n times this, since not every
+// driver reports the source string numbers in #line directives
+const LINES_PER_FILE: usize = 10000;
+
+/// Shader source ready to compile, and what it was put together from.
+pub struct Preprocessed {
+    pub source: String,
+    /// The files `source` was assembled from, in the order they were
+    /// included. The main file comes first.
+    pub files: Vec<String>,
+    // the #version, which decides how #line counts
+    version: u32,
+}
+
+/// Prepares the shader in the file `name`, with `load` reading it and
+/// anything it includes:
+///
+/// - `#include "file"` lines are replaced by the file's contents. Every file
+///   is included at most once, so includes need no guards of their own.
+/// - every `(name, value)` in `defines` becomes a `#define`, right after
+///   `#version`.
+/// - `#line` directives let `Preprocessed::map_error` point compiler
+///   messages at the right file and line.
+pub fn preprocess<'a, F>(name: &str, defines: &[(&str, &str)], mut load: F) -> Result<Preprocessed, Error>
+where
+    F: FnMut(&str) -> Result<Cow<'a, str>, Error>,
+{
+    let main = load(name)?;
+    let mut out = Preprocessed {
+        source: String::new(),
+        files: vec![name.to_string()],
+        version: 110,
+    };
+
+    // defines have to go after #version, which comes before anything but
+    // comments
+    let lines: Vec<&str> = main.lines().collect();
+    let start = match lines.iter().position(|line| line.trim_start().starts_with("#version")) {
+        Some(version) => {
+            out.version = lines[version].trim_start()["#version".len()..].split_whitespace().next()
+                .and_then(|version| version.parse().ok())
+                .unwrap_or(out.version);
+            version + 1
+        },
+        None => 0,
+    };
+    for line in &lines[..start] {
+        out.source.push_str(line);
+        out.source.push('\n');
+    }
+
+    for (name, value) in defines {
+        out.source.push_str(&format!("#define {} {}\n", name, value));
+    }
+    out.source.push_str(&out.line_directive(start + 1, 0));
+
+    expand(&mut out, 0, &lines[start..], start + 1, &mut load)?;
+
+    Ok(out)
+}
+
+// appends `lines` of file `file`, the first being line `first_line`, and
+// recurses into includes
+fn expand<'a, F>(out: &mut Preprocessed, file: usize, lines: &[&str], first_line: usize, load: &mut F) -> Result<(), Error>
+where
+    F: FnMut(&str) -> Result<Cow<'a, str>, Error>,
+{
+    for (number, line) in (first_line..).zip(lines) {
+        let include = match line.trim_start().strip_prefix("#include") {
+            Some(include) => include.trim(),
+            None => {
+                out.source.push_str(line);
+                out.source.push('\n');
+                continue;
+            },
+        };
+
+        let included = match include.strip_prefix('"').and_then(|include| include.strip_suffix('"')) {
+            Some(included) => included,
+            None => return Err(Error::Preprocess {
+                source: out.files[file].clone(),
+                line: number,
+                message: format!("expected #include \"file\", got #include {}", include),
+            }),
+        };
+
+        if !out.files.iter().any(|file| file == included) {
+            let contents = load(included)?;
+            out.files.push(included.to_string());
+
+            out.source.push_str(&out.line_directive(1, out.files.len() - 1));
+            expand(out, out.files.len() - 1, &contents.lines().collect::<Vec<_>>(), 1, load)?;
+        }
+        // back to the line after the #include
+        out.source.push_str(&out.line_directive(number + 1, file));
+    }
+
+    Ok(())
+}
+
+impl Preprocessed {
+    // makes the next line number `line` of `file`
+    fn line_directive(&self, line: usize, file: usize) -> String {
+        // before 3.30, #line set the number of its own line
+        let line = file*LINES_PER_FILE + line - if self.version < 330 { 1 } else { 0 };
+        format!("#line {} {}\n", line, file)
+    }
+
+    /// Turns the locations in the log of a compile error back into file
+    /// names and lines, for the usual log formats (`0:12(3)`, `0(12)` and
+    /// `ERROR: 0:12:`). Other errors are returned as they are.
+    pub fn map_error(&self, err: Error) -> Error {
+        match err {
+            Error::Compile { source, stage, log } => Error::Compile {
+                source,
+                stage,
+                log: log.lines().map(|line| self.map_log_line(line)).collect::<Vec<_>>().join("\n"),
+            },
+            err => err,
+        }
+    }
+
+    fn map_log_line(&self, line: &str) -> String {
+        let prefix_len = ["ERROR: ", "WARNING: "].iter()
+            .find(|prefix| line.starts_with(*prefix))
+            .map_or(0, |prefix| prefix.len());
+        let (prefix, rest) = line.split_at(prefix_len);
+
+        // the source string number, then the line
+        let source_len = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if source_len == 0 || !matches!(rest.as_bytes().get(source_len), Some(b':') | Some(b'(')) {
+            return line.to_string();
+        }
+        let after_source = &rest[source_len + 1..];
+        let line_len = after_source.bytes().take_while(u8::is_ascii_digit).count();
+
+        match after_source[..line_len].parse::<usize>() {
+            Ok(number) if number / LINES_PER_FILE < self.files.len() => {
+                let separator = &rest[source_len..source_len + 1];
+                let file = &self.files[number / LINES_PER_FILE];
+                format!("{}{}{}{}{}", prefix, file, separator, number % LINES_PER_FILE, &after_source[line_len..])
+            },
+            _ => line.to_string(),
+        }
+    }
+}
@@ -1,5 +1,10 @@
 use gl::types::*;
-use std::ffi::CStr;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+use super::{Error, Uniform, UniformValue};
 
 #[derive(Clone, Copy, Debug)]
 pub enum ShaderType {
@@ -8,10 +13,22 @@ pub enum ShaderType {
     Fragment,
 }
 
+impl fmt::Display for ShaderType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ShaderType::Vertex => "vertex",
+            ShaderType::Geometry => "geometry",
+            ShaderType::Fragment => "fragment",
+        })
+    }
+}
+
 pub struct Shader(GLuint);
 
 impl Shader {
-    pub fn compile(type_: ShaderType, source: &str) -> Result<Shader, String> {
+    /// Compiles `source`. `source_name` says where it came from, for
+    /// error messages.
+    pub fn compile(type_: ShaderType, source_name: &str, source: &str) -> Result<Shader, Error> {
         // creates the shader object
         let name = unsafe {
             gl::CreateShader(match type_ {
@@ -49,12 +66,23 @@ impl Shader {
 
             let mut buf = vec![0u8; len];
             unsafe {
-                gl::GetShaderInfoLog(name, len as GLsizei, 0 as *mut _, buf.as_mut_ptr() as *mut GLchar);
+                gl::GetShaderInfoLog(name, len as GLsizei, std::ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
             }
 
-            Err(String::from_utf8_lossy(&buf).to_string())
+            unsafe {
+                gl::DeleteShader(name);
+            }
+
+            Err(Error::Compile {
+                source: source_name.to_string(),
+                stage: type_,
+                log: String::from_utf8_lossy(&buf).trim_end_matches('\0').to_string(),
+            })
         } else {
-            Ok(Shader(name))
+            let shader = Shader(name);
+            shader.set_label(source_name);
+
+            Ok(shader)
         }
     }
 
@@ -71,7 +99,12 @@ impl Drop for Shader {
     }
 }
 
-pub struct ShaderProgram(GLuint);
+pub struct ShaderProgram {
+    name: GLuint,
+    // bumped by every successful `relink`, so uniforms know to look up
+    // their locations again
+    generation: Cell<u32>,
+}
 
 impl ShaderProgram {
     pub fn new() -> ShaderProgram {
@@ -80,68 +113,204 @@ impl ShaderProgram {
         };
         assert!(name != 0, "could not create shader program object");
 
-        ShaderProgram(name)
+        ShaderProgram {
+            name,
+            generation: Cell::new(0),
+        }
     }
 
     pub fn attach(&self, shader: &Shader) {
         unsafe {
-            gl::AttachShader(self.0, shader.name());
+            gl::AttachShader(self.name, shader.name());
         }
     }
 
-    pub fn link(&self) -> Result<(), String> {
+    /// Links the attached shaders. `program_name` says what the program
+    /// is for, in error messages.
+    pub fn link(&self, program_name: &str) -> Result<(), Error> {
         unsafe {
-            gl::LinkProgram(self.0);
+            gl::LinkProgram(self.name);
         }
 
         let state = unsafe {
             let mut state: GLint = 0;
-            gl::GetProgramiv(self.0, gl::LINK_STATUS, &mut state as *mut GLint);
+            gl::GetProgramiv(self.name, gl::LINK_STATUS, &mut state as *mut GLint);
             state
         };
 
         if state == gl::FALSE as GLint {
             let len = unsafe {
                 let mut len: GLint = 0;
-                gl::GetProgramiv(self.0, gl::INFO_LOG_LENGTH, &mut len as *mut GLint);
+                gl::GetProgramiv(self.name, gl::INFO_LOG_LENGTH, &mut len as *mut GLint);
                 len as usize
             };
 
             let mut buf = vec![0u8; len];
             unsafe {
-                gl::GetProgramInfoLog(self.0, len as GLsizei, 0 as *mut _, buf.as_mut_ptr() as *mut GLchar);
+                gl::GetProgramInfoLog(self.name, len as GLsizei, std::ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
             }
 
-            Err(String::from_utf8_lossy(&buf).to_string())
+            Err(Error::Link {
+                program: program_name.to_string(),
+                log: String::from_utf8_lossy(&buf).trim_end_matches('\0').to_string(),
+            })
         } else {
+            self.set_label(program_name);
+
             Ok(())
         }
     }
 
-    pub fn use_(&self) {
+    /// Replaces the attached shaders with `shaders` and links again. If
+    /// that fails, the program is left as it was.
+    ///
+    /// Attributes keep their locations, so vaos set up for the program stay
+    /// valid. Every uniform is reset, and `Uniform` handles look up their
+    /// new locations the next time they're set.
+    pub fn relink(&self, shaders: &[&Shader], program_name: &str) -> Result<(), Error> {
+        let attribs = self.active_attribs();
+
+        // a failed link would leave the program unusable, so try on a
+        // scratch program first
+        let scratch = ShaderProgram::new();
+        for shader in shaders {
+            scratch.attach(shader);
+        }
+        scratch.bind_attrib_locations(&attribs);
+        scratch.link(program_name)?;
+
+        let mut attached = [0; 8];
+        let mut count = 0;
         unsafe {
-            gl::UseProgram(self.0)
+            gl::GetAttachedShaders(self.name, attached.len() as GLsizei, &mut count, attached.as_mut_ptr());
+            for &shader in &attached[..count as usize] {
+                gl::DetachShader(self.name, shader);
+            }
         }
+        for shader in shaders {
+            self.attach(shader);
+        }
+        self.bind_attrib_locations(&attribs);
+        self.link(program_name)?;
+
+        self.generation.set(self.generation.get() + 1);
+        Ok(())
     }
 
-    pub fn get_uniform_location(&self, uniform: &CStr) -> GLint {
-        let loc = unsafe {
-            gl::GetUniformLocation(self.0, uniform.as_ptr())
-        };
-        assert!(loc != -1, "invalid uniform variable name");
+    // names and locations of the active attributes
+    fn active_attribs(&self) -> Vec<(CString, GLuint)> {
+        let mut count = 0;
+        unsafe {
+            gl::GetProgramiv(self.name, gl::ACTIVE_ATTRIBUTES, &mut count);
+        }
+
+        (0..count as GLuint)
+            .filter_map(|i| {
+                let mut buf = [0u8; 256];
+                let (mut len, mut size, mut type_) = (0, 0, 0);
+                unsafe {
+                    gl::GetActiveAttrib(self.name, i, buf.len() as GLsizei, &mut len, &mut size, &mut type_, buf.as_mut_ptr() as *mut GLchar);
+                }
+                let name = CString::new(&buf[..len as usize]).ok()?;
+
+                // built-ins like gl_VertexID have no location
+                let location = unsafe {
+                    gl::GetAttribLocation(self.name, name.as_ptr())
+                };
+                if location < 0 {
+                    return None;
+                }
+
+                Some((name, location as GLuint))
+            })
+            .collect()
+    }
+
+    /// Gives attributes the locations they have in `other`, from the next
+    /// link on, so vaos set up for `other` work with this program too.
+    pub fn share_attrib_locations(&self, other: &ShaderProgram) {
+        self.bind_attrib_locations(&other.active_attribs());
+    }
 
-        loc
+    fn bind_attrib_locations(&self, attribs: &[(CString, GLuint)]) {
+        for (name, location) in attribs {
+            unsafe {
+                gl::BindAttribLocation(self.name, *location, name.as_ptr());
+            }
+        }
+    }
+
+    /// Makes the program current. Uniforms can only be set through the
+    /// returned guard, so they always go to the right program.
+    pub fn bind(&self) -> BoundProgram<'_> {
+        unsafe {
+            gl::UseProgram(self.name)
+        }
+
+        BoundProgram {
+            program: self,
+        }
+    }
+
+    /// Looks up a uniform the program must have. Uniforms the shader never
+    /// reads are optimised out and count as missing.
+    pub fn uniform<T: UniformValue>(&self, name: &CStr) -> Result<Uniform<T>, Error> {
+        let uniform = self.optional_uniform(name);
+        if uniform.is_active() {
+            Ok(uniform)
+        } else {
+            Err(Error::MissingUniform(name.to_string_lossy().into_owned()))
+        }
+    }
+
+    /// Like `uniform`, but a missing uniform gives a handle that ignores
+    /// whatever it is set to.
+    pub fn optional_uniform<T: UniformValue>(&self, name: &CStr) -> Uniform<T> {
+        Uniform::new(self, name)
+    }
+
+    /// Location of the attribute `name`, or `None` if the program has no
+    /// such attribute or never reads it.
+    pub fn attrib_location(&self, name: &CStr) -> Option<GLuint> {
+        let location = unsafe {
+            gl::GetAttribLocation(self.name, name.as_ptr())
+        };
+        GLuint::try_from(location).ok()
     }
 
     pub fn name(&self) -> GLuint {
-        self.0
+        self.name
+    }
+
+    /// How many times the program was relinked.
+    pub fn generation(&self) -> u32 {
+        self.generation.get()
+    }
+}
+
+impl Default for ShaderProgram {
+    fn default() -> ShaderProgram {
+        ShaderProgram::new()
     }
 }
 
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteProgram(self.0)
+            gl::DeleteProgram(self.name)
         }
     }
 }
+
+/// A program in use, as returned by `ShaderProgram::bind`.
+pub struct BoundProgram<'a> {
+    program: &'a ShaderProgram,
+}
+
+impl BoundProgram<'_> {
+    /// Sets a uniform of this program, unless it already holds `value`.
+    pub fn set<T: UniformValue>(&self, uniform: &Uniform<T>, value: T) {
+        debug_assert_eq!(uniform.program(), self.program.name, "uniform belongs to another program");
+        uniform.set(self.program, value);
+    }
+}
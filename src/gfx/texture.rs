@@ -1,5 +1,117 @@
 use gl::types::*;
-use std::io::Read;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use super::Error;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+/// How a texture is stored and sampled.
+#[derive(Clone, Copy, Debug)]
+pub struct TextureOptions {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub wrap: Wrap,
+    /// Generates mipmaps. When minifying, a `Linear` min filter blends
+    /// between them and a `Nearest` one picks the closest.
+    pub mipmaps: bool,
+    /// Stores the pixels as sRGB, so sampling returns linear values.
+    pub srgb: bool,
+    /// Multiplies color by alpha on upload, for blending with
+    /// `ONE, ONE_MINUS_SRC_ALPHA`.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> TextureOptions {
+        TextureOptions {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            wrap: Wrap::Repeat,
+            mipmaps: true,
+            srgb: false,
+            premultiply_alpha: false,
+        }
+    }
+}
+
+/// Decodes a png of any color type and bit depth into RGBA8 pixels, rows
+/// top to bottom. Palettes and `tRNS` transparency are expanded, and 16-bit
+/// channels are cut down to 8 bits.
+pub fn decode_png<R: Read>(input: R) -> Result<(u32, u32, Vec<u8>), Error> {
+    use png::{BitDepth, ColorType, Transformations};
+
+    let decode_error = |err: png::DecodingError| Error::ImageDecode {
+        path: None,
+        error: io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    };
+
+    let mut decoder = png::Decoder::new(input);
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(decode_error)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buf).map_err(decode_error)?;
+
+    let (color_type, bit_depth) = reader.output_color_type();
+    if bit_depth != BitDepth::Eight {
+        return Err(Error::UnsupportedFormat {
+            path: None,
+            format: format!("{:?} bit {:?} after expansion", bit_depth, color_type),
+        });
+    }
+
+    let pixels = match color_type {
+        ColorType::RGBA => buf,
+        ColorType::RGB => buf.chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => buf.chunks_exact(2)
+            .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        ColorType::Grayscale => buf.iter()
+            .flat_map(|&g| [g, g, g, 255])
+            .collect(),
+        ColorType::Indexed => return Err(Error::UnsupportedFormat {
+            path: None,
+            format: "indexed color without a palette".to_string(),
+        }),
+    };
+
+    Ok((info.width, info.height, pixels))
+}
+
+/// Multiplies the color of RGBA8 pixels by their alpha. sRGB colors are
+/// multiplied in linear space, the way the sampler will read them.
+fn premultiply(pixels: &[u8], srgb: bool) -> Vec<u8> {
+    fn to_linear(c: f32) -> f32 {
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    }
+    fn to_srgb(c: f32) -> f32 {
+        if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+    }
+
+    let multiply = |c: u8, a: u8| if srgb {
+        (to_srgb(to_linear(c as f32 / 255.0) * (a as f32 / 255.0)) * 255.0).round() as u8
+    } else {
+        (c as u32*a as u32 / 255) as u8
+    };
+
+    pixels.chunks_exact(4)
+        .flat_map(|p| [multiply(p[0], p[3]), multiply(p[1], p[3]), multiply(p[2], p[3]), p[3]])
+        .collect()
+}
 
 pub struct Texture(GLuint);
 
@@ -12,35 +124,116 @@ impl Texture {
         })
     }
 
-    // THIS WILL UNWRAP RESULTS!!!
-    // will also leave the texture bound
-    pub fn load_from_png<R: Read>(input: R) -> Texture {
-        use png::{ColorType, BitDepth};
+    /// Decodes a png of any format (see `decode_png`) into a new texture.
+    pub fn load_from_png<R: Read>(input: R, options: &TextureOptions) -> Result<Texture, Error> {
+        let (width, height, pixels) = decode_png(input)?;
+        Ok(Self::from_rgba8(width, height, &pixels, options))
+    }
+
+    /// Like `load_from_png`, naming `path` in errors.
+    pub fn load_from_file<P: AsRef<Path>>(path: P, options: &TextureOptions) -> Result<Texture, Error> {
+        let path = path.as_ref();
+        let with_path = |err| match err {
+            Error::ImageDecode { error, .. } => Error::ImageDecode { path: Some(path.into()), error },
+            Error::UnsupportedFormat { format, .. } => Error::UnsupportedFormat { path: Some(path.into()), format },
+            err => err,
+        };
 
-        let decoder = png::Decoder::new(input);
-        let (info, mut reader) = decoder.read_info().unwrap();
-        let mut buf = vec![0; info.buffer_size()];
-        reader.next_frame(&mut buf).unwrap();
-        
-        assert_eq!(info.color_type, ColorType::RGBA, "unimplemented png color type");
-        assert_eq!(info.bit_depth, BitDepth::Eight, "unimplemented png bit depth");
+        let file = fs::File::open(path).map_err(|error| with_path(Error::ImageDecode { path: None, error }))?;
+        Self::load_from_png(io::BufReader::new(file), options).map_err(with_path)
+    }
+
+    /// Allocates a texture without pixels, to render into. Depth formats
+    /// get a depth texture. Unlike `from_rgba8` it leaves the texture bound
+    /// with default parameters, for the caller to set.
+    pub fn new_empty(internal_format: GLenum, width: u32, height: u32) -> Texture {
+        let (format, type_) = match internal_format {
+            gl::DEPTH_COMPONENT16 | gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32F => (gl::DEPTH_COMPONENT, gl::FLOAT),
+            _ => (gl::RGBA, gl::UNSIGNED_BYTE),
+        };
+
+        let texture = Self::new();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture.name());
+            gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as GLint,
+                width as GLint, height as GLint, 0,
+                format, type_, std::ptr::null());
+        }
+
+        texture
+    }
+
+    /// Uploads RGBA8 pixels, rows top to bottom, into a new texture.
+    pub fn from_rgba8(width: u32, height: u32, pixels: &[u8], options: &TextureOptions) -> Texture {
+        assert_eq!(pixels.len(), 4*(width*height) as usize, "wrong pixel buffer size");
+
+        let premultiplied;
+        let pixels = if options.premultiply_alpha {
+            premultiplied = premultiply(pixels, options.srgb);
+            &premultiplied[..]
+        } else {
+            pixels
+        };
+
+        let internal_format = if options.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
 
         let texture = Self::new();
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, texture.name());
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as GLint,
-                info.width as GLint, info.height as GLint, 0,
-                gl::RGBA, gl::UNSIGNED_BYTE, buf.as_ptr() as *const _);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as GLint,
+                width as GLint, height as GLint, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const _);
+        }
+        texture.set_options(options);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
         texture
     }
 
+    /// Sets filtering and wrapping of the texture, which must be bound, and
+    /// generates mipmaps if asked to.
+    fn set_options(&self, options: &TextureOptions) {
+        let filter = |filter| match filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+        let min_filter = match (options.mipmaps, options.min_filter) {
+            (false, min_filter) => filter(min_filter),
+            (true, Filter::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (true, Filter::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        };
+        let wrap = match options.wrap {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+        };
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter(options.mag_filter) as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
+
+            if options.mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+        }
+    }
+
     pub fn name(&self) -> GLuint {
         self.0
     }
 }
 
+impl Default for Texture {
+    fn default() -> Texture {
+        Texture::new()
+    }
+}
+
 impl Drop for Texture {
     fn drop(&mut self) {
         unsafe {
@@ -48,3 +241,16 @@ impl Drop for Texture {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::premultiply;
+
+    #[test]
+    fn premultiplies_srgb_in_linear_space() {
+        let pixels = [255, 128, 0, 128, 200, 100, 50, 255, 9, 9, 9, 0];
+        assert_eq!(premultiply(&pixels, false), [128, 64, 0, 128, 200, 100, 50, 255, 0, 0, 0, 0]);
+        // half of linear white is about 188 in sRGB, not 128
+        assert_eq!(premultiply(&pixels, true), [188, 93, 0, 128, 200, 100, 50, 255, 0, 0, 0, 0]);
+    }
+}
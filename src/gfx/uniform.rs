@@ -0,0 +1,166 @@
+use gl::types::*;
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
+
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+
+use super::ShaderProgram;
+
+/// The texture unit a `sampler2D` (or other sampler) reads from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TextureUnit(pub u32);
+
+/// A type that can be uploaded to a uniform of the matching GLSL type.
+pub trait UniformValue: Copy + PartialEq {
+    /// Uploads `self` to `location` of the program in use.
+    fn upload(&self, location: GLint);
+}
+
+impl UniformValue for f32 {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform1f(location, *self);
+        }
+    }
+}
+
+impl UniformValue for i32 {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform1i(location, *self);
+        }
+    }
+}
+
+impl UniformValue for bool {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform1i(location, *self as GLint);
+        }
+    }
+}
+
+impl UniformValue for TextureUnit {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform1i(location, self.0 as GLint);
+        }
+    }
+}
+
+impl UniformValue for Vector2<f32> {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform2fv(location, 1, self.as_ptr());
+        }
+    }
+}
+
+impl UniformValue for Vector3<f32> {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform3fv(location, 1, self.as_ptr());
+        }
+    }
+}
+
+impl UniformValue for Vector4<f32> {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform4fv(location, 1, self.as_ptr());
+        }
+    }
+}
+
+// for `uniform float name[N]`
+impl<const N: usize> UniformValue for [f32; N] {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform1fv(location, N as GLsizei, self.as_ptr());
+        }
+    }
+}
+
+// for `uniform vec4 name[N]`
+impl<const N: usize> UniformValue for [Vector4<f32>; N] {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform4fv(location, N as GLsizei, self.as_ptr() as *const GLfloat);
+        }
+    }
+}
+
+impl UniformValue for Matrix4<f32> {
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::UniformMatrix4fv(location, 1, gl::FALSE, self.as_ptr());
+        }
+    }
+}
+
+/// A uniform of one particular program, set through
+/// `BoundProgram::set`. Remembers the last value it uploaded, so setting the
+/// same value again is free.
+///
+/// Handles for uniforms the program doesn't have (see
+/// `ShaderProgram::optional_uniform`) ignore every value.
+///
+/// After the program is relinked, the handle looks its location up again
+/// and forgets the last value.
+pub struct Uniform<T: UniformValue> {
+    program: GLuint,
+    name: CString,
+    location: Cell<GLint>,
+    // the program generation `location` belongs to
+    generation: Cell<u32>,
+    last: Cell<Option<T>>,
+}
+
+impl<T: UniformValue> Uniform<T> {
+    pub(super) fn new(program: &ShaderProgram, name: &CStr) -> Uniform<T> {
+        let location = unsafe {
+            gl::GetUniformLocation(program.name(), name.as_ptr())
+        };
+
+        Uniform {
+            program: program.name(),
+            name: name.to_owned(),
+            location: Cell::new(location),
+            generation: Cell::new(program.generation()),
+            last: Cell::new(None),
+        }
+    }
+
+    /// False if the program has no such uniform, e.g. because it was
+    /// optimised out.
+    pub fn is_active(&self) -> bool {
+        self.location.get() != -1
+    }
+
+    pub fn location(&self) -> GLint {
+        self.location.get()
+    }
+
+    pub(super) fn program(&self) -> GLuint {
+        self.program
+    }
+
+    pub(super) fn set(&self, program: &ShaderProgram, value: T) {
+        if self.generation.get() != program.generation() {
+            let location = unsafe {
+                gl::GetUniformLocation(program.name(), self.name.as_ptr())
+            };
+            self.location.set(location);
+            self.generation.set(program.generation());
+            self.last.set(None);
+        }
+
+        if !self.is_active() || self.last.get() == Some(value) {
+            return;
+        }
+
+        value.upload(self.location.get());
+        self.last.set(Some(value));
+    }
+}
@@ -22,6 +22,12 @@ impl VertexArrayObject {
     }
 }
 
+impl Default for VertexArrayObject {
+    fn default() -> VertexArrayObject {
+        VertexArrayObject::new()
+    }
+}
+
 impl Drop for VertexArrayObject {
     fn drop(&mut self) {
         unsafe {
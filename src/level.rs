@@ -1,13 +1,224 @@
 pub struct Level {
-    pub bg_color: (f32, f32, f32),
+    pub background: Background,
+    pub lighting: Lighting,
+    pub lanterns: Vec<Lantern>,
     pub platforms: Vec<Platform>,
 }
 
+impl Level {
+    /// The level loaded by default.
+    pub fn demo() -> Level {
+        let platform_color = (0.38, 0.15, 0.34);
+
+        Level {
+            background: Background {
+                sky_top: (0.08, 0.07, 0.16),
+                sky_bottom: (0.32, 0.18, 0.3),
+                layers: vec![
+                    BackgroundLayer::hills((0.2, 0.13, 0.25), 0.45, 0.08, 3, 0.1),
+                    BackgroundLayer::skyline((0.15, 0.1, 0.19), 0.3, 0.12, 24, 0.2),
+                    BackgroundLayer::hills((0.11, 0.08, 0.14), 0.18, 0.05, 5, 0.35),
+                ],
+            },
+            lighting: Lighting {
+                // a haze a little lighter than the sky near the horizon
+                fog: Fog {
+                    color: (0.45, 0.33, 0.45),
+                    start: -6.0,
+                    end: 10.0,
+                    density: 0.7,
+                },
+                ..Lighting::default()
+            },
+            lanterns: vec![
+                // in front of the platforms to the south west and north east
+                Lantern::new((-5.5, -11.0, -3.5), (1.0, 0.6, 0.25), 5.0),
+                Lantern::new((5.5, 11.0, 2.5), (0.3, 0.5, 1.0), 5.0),
+            ],
+            platforms: vec![
+                Platform::new((0.0, 0.0, -6.0), (8.0, 8.0), 1.0, platform_color).with_texture("test"),
+                Platform::new((0.0, 0.0, 6.0), (4.0, 4.0), 12.0, platform_color).with_texture("test").with_shininess(16.0),
+                // a ledge off the tower, shading its sunny side
+                Platform::new((1.0, -3.0, 1.0), (2.0, 2.0), 0.5, platform_color),
+                Platform::new((-5.0, -9.0, -3.0), (2.0, 2.0), 1.0, platform_color),
+                Platform::new((9.0, -5.0, 0.0), (2.0, 2.0), 1.0, platform_color),
+                Platform::new((5.0, 9.0, 3.0), (2.0, 2.0), 1.0, platform_color),
+                Platform::new((-9.0, 5.0, 6.0), (2.0, 2.0), 1.0, platform_color),
+            ],
+        }
+    }
+
+    /// A `side` by `side` grid of small platforms at varying heights, for
+    /// measuring rendering performance.
+    pub fn benchmark(side: usize) -> Level {
+        let mut platforms = Vec::with_capacity(side*side);
+        for i in 0..side {
+            for j in 0..side {
+                let x = 3.0*(i as f32 - 0.5*side as f32);
+                let y = 3.0*(j as f32 - 0.5*side as f32);
+                let z = 4.0*((i + 2*j) % 7) as f32 - 12.0;
+
+                let color = (
+                    0.2 + 0.6*(i as f32 / side as f32),
+                    0.15,
+                    0.2 + 0.6*(j as f32 / side as f32),
+                );
+
+                platforms.push(Platform::new((x, y, z), (2.0, 2.0), 1.0, color));
+            }
+        }
+
+        Level {
+            background: Background::plain((0.1, 0.1, 0.1)),
+            lighting: Lighting::default(),
+            lanterns: Vec::new(),
+            platforms,
+        }
+    }
+}
+
+/// What's drawn behind the platforms: a sky fading from `sky_top` to
+/// `sky_bottom`, and in front of it silhouettes of far-off scenery.
+pub struct Background {
+    pub sky_top: (f32, f32, f32),
+    pub sky_bottom: (f32, f32, f32),
+    /// Drawn in order, so the farthest comes first.
+    pub layers: Vec<BackgroundLayer>,
+}
+
+impl Background {
+    /// A sky of one color, with nothing in it.
+    pub fn plain(color: (f32, f32, f32)) -> Self {
+        Self {
+            sky_top: color,
+            sky_bottom: color,
+            layers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LayerShape {
+    /// Rolling, from a few waves.
+    Hills,
+    /// Flat topped blocks of random heights.
+    Skyline,
+}
+
+/// A silhouette wrapped all the way around the level, which scrolls
+/// sideways as the camera turns.
+///
+/// Heights are fractions of the screen's height from its bottom.
+pub struct BackgroundLayer {
+    pub shape: LayerShape,
+    pub color: (f32, f32, f32),
+    /// Average height of the top edge.
+    pub height: f32,
+    /// How far the top edge strays from `height`, up and down.
+    pub amplitude: f32,
+    /// Hills or blocks all the way around.
+    pub bumps: u32,
+    /// Screen widths scrolled per quarter turn of the camera. Nearer layers
+    /// scroll more.
+    pub parallax: f32,
+    /// Varies the shape between layers that are otherwise the same.
+    pub seed: f32,
+}
+
+impl BackgroundLayer {
+    pub fn hills(color: (f32, f32, f32), height: f32, amplitude: f32, bumps: u32, parallax: f32) -> Self {
+        Self {
+            shape: LayerShape::Hills,
+            color,
+            height,
+            amplitude,
+            bumps,
+            parallax,
+            seed: height,
+        }
+    }
+
+    pub fn skyline(color: (f32, f32, f32), height: f32, amplitude: f32, bumps: u32, parallax: f32) -> Self {
+        Self {
+            shape: LayerShape::Skyline,
+            ..Self::hills(color, height, amplitude, bumps, parallax)
+        }
+    }
+}
+
+/// Light shared by the whole level. Directions are in world space, so the
+/// lighting stays put as the camera turns.
+pub struct Lighting {
+    pub ambient: (f32, f32, f32),
+    /// The direction sunlight travels in, e.g. straight down is (0, 0, -1).
+    pub sun_direction: (f32, f32, f32),
+    pub sun_color: (f32, f32, f32),
+    pub fog: Fog,
+}
+
+impl Default for Lighting {
+    /// A sun high up behind the camera's starting position.
+    fn default() -> Self {
+        Self {
+            ambient: (0.55, 0.55, 0.6),
+            sun_direction: (0.35, 0.55, -0.76),
+            sun_color: (0.65, 0.62, 0.55),
+            fog: Fog::default(),
+        }
+    }
+}
+
+/// Fades what's far from the camera towards `color`, to tell near and far
+/// apart. Depth is measured along the direction the camera looks in, from
+/// the plane through the origin.
+pub struct Fog {
+    pub color: (f32, f32, f32),
+    /// Depth where the fog starts, negative for in front of the origin.
+    pub start: f32,
+    /// Depth where it stops getting thicker.
+    pub end: f32,
+    /// How much of `color` shows from `end` on, from 0 to 1.
+    pub density: f32,
+}
+
+impl Default for Fog {
+    /// No fog at all.
+    fn default() -> Self {
+        Self {
+            color: (0.0, 0.0, 0.0),
+            start: 0.0,
+            end: 1.0,
+            density: 0.0,
+        }
+    }
+}
+
+/// A point light, fading out up to `radius`.
+pub struct Lantern {
+    pub position: (f32, f32, f32),
+    pub color: (f32, f32, f32),
+    pub radius: f32,
+}
+
+impl Lantern {
+    pub fn new(position: (f32, f32, f32), color: (f32, f32, f32), radius: f32) -> Self {
+        Self {
+            position,
+            color,
+            radius,
+        }
+    }
+}
+
 pub struct Platform {
     pub surface_center: (f32, f32, f32),
     pub surface_dim: (f32, f32),
     pub height: f32,
     pub color: (f32, f32, f32),
+    /// Name of a texture registered in `Resources`, tinted by `color`.
+    pub texture: Option<String>,
+    /// Specular exponent; zero for a matte surface.
+    pub shininess: f32,
 }
 
 impl Platform {
@@ -17,9 +228,22 @@ impl Platform {
             surface_dim,
             height,
             color,
+            texture: None,
+            shininess: 0.0,
         }
     }
 
+    pub fn with_texture(mut self, texture: &str) -> Self {
+        self.texture = Some(texture.to_string());
+        self
+    }
+
+    pub fn with_shininess(mut self, shininess: f32) -> Self {
+        self.shininess = shininess;
+        self
+    }
+
+    #[allow(clippy::needless_bool)]
     pub fn intersection_x(&self, x: f32, xdim: f32, z_lower: f32, z_upper: f32) -> bool {
         if (x - self.surface_center.0).abs() > 0.5*(xdim + self.surface_dim.0) {
             false
@@ -30,6 +254,7 @@ impl Platform {
         }
     }
 
+    #[allow(clippy::needless_bool)]
     pub fn intersection_y(&self, y: f32, ydim: f32, z_lower: f32, z_upper: f32) -> bool {
         if (y - self.surface_center.1).abs() > 0.5*(ydim + self.surface_dim.1) {
             false
@@ -8,3 +8,59 @@ macro_rules! c_str {
         }
     };
 }
+
+/// Declares a `#[repr(C)]` vertex struct and implements `gfx::VertexLayout`
+/// for it. Each field names the shader input it feeds:
+///
+/// ```ignore
+/// vertex_layout! {
+///     pub struct Vertex {
+///         pub position: [f32; 3] = "inPosition",
+///         pub tex_coords: [f32; 2] = "inTexCoords",
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! vertex_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $type:ty = $attrib:literal),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug)]
+        #[repr(C)]
+        $vis struct $name {
+            $($field_vis $field: $type),*
+        }
+
+        impl $crate::gfx::VertexLayout for $name {
+            const ATTRIBS: &'static [$crate::gfx::VertexAttrib] = &[
+                $($crate::gfx::VertexAttrib {
+                    name: $attrib,
+                    type_: <$type as $crate::gfx::AttribValue>::TYPE,
+                    offset: ::std::mem::offset_of!($name, $field),
+                }),*
+            ];
+        }
+    };
+}
+
+/// Queues a shape with one of the functions in `debug_draw`, returning its
+/// `Shape`. In release builds nothing is queued and the arguments aren't
+/// evaluated, so they can be as expensive as needed:
+///
+/// ```ignore
+/// debug_draw!(text(position, format!("{:?}", player.movement), (1.0, 1.0, 1.0))).for_seconds(1.0);
+/// ```
+#[macro_export]
+macro_rules! debug_draw {
+    ($shape:ident($($arg:expr),* $(,)?)) => {
+        if cfg!(debug_assertions) {
+            $crate::debug_draw::$shape($($arg),*)
+        } else {
+            $crate::debug_draw::Shape::none()
+        }
+    };
+}
@@ -1,7 +1,11 @@
 mod macros;
 pub mod gfx;
 pub mod resources;
-use resources::Resources;
+use resources::{Resources, ShaderSources, TextStyle};
+use resources::font::Align;
+use resources::postprocess::PostProcessor;
+mod settings;
+use settings::Settings;
 
 mod camera;
 use crate::camera::*;
@@ -10,30 +14,76 @@ mod level;
 use level::*;
 mod player;
 use player::*;
-
+mod animation;
+use animation::PlayerAnimator;
+mod capture;
+use capture::ClipRecorder;
+mod particles;
+use particles::ParticleSystem;
+mod debug_overlay;
+mod debug_draw;
+
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+#[cfg(test)]
+mod tests;
+
+const STARTING_POSITION: (f32, f32, f32) = (-3.0, -3.0, -5.0);
+// how far above a platform the player's shadow on it disappears
+const DROP_SHADOW_FADE_HEIGHT: f32 = 6.0;
+
 struct Game {
+    shaders: ShaderSources,
     res: Resources,
+    post: PostProcessor,
 
     camera: Camera,
     level: Level,
     player: Player,
+    player_animator: PlayerAnimator,
+    // where the player respawns
+    starting_position: (f32, f32, f32),
+    particles: ParticleSystem,
+    // a fraction of the next dust particle
+    run_dust_carry: f32,
 
     last_tick: Instant,
+    // frames per second, smoothed over the last few ticks
+    fps: f32,
+    pub show_fps: bool,
+    pub show_debug_overlay: bool,
+    // draws platforms the way they were drawn before instancing
+    pub per_platform_draws: bool,
 }
 
 impl Game {
-    pub fn new(level: Level, player_pos: (f32, f32, f32)) -> Game {
-        Game {
-            res: Resources::new(),
+    pub fn new(shaders: ShaderSources, level: Level, player_pos: (f32, f32, f32)) -> Result<Game, gfx::Error> {
+        let mut res = Resources::new(&shaders)?;
+        res.set_background(&level.background);
+        res.set_lighting(&level.lighting, &level.lanterns);
+        res.set_platforms(&level.platforms);
+        let post = PostProcessor::new(&shaders)?;
+
+        Ok(Game {
+            shaders,
+            res,
+            post,
 
             camera: Camera::new(Perspective::S),
             level,
             player: Player::new(player_pos),
+            player_animator: PlayerAnimator::new(),
+            starting_position: player_pos,
+            particles: ParticleSystem::new(),
+            run_dust_carry: 0.0,
 
             last_tick: Instant::now(),
-        }
+            fps: 0.0,
+            show_fps: false,
+            show_debug_overlay: false,
+            per_platform_draws: false,
+        })
     }
 
     pub fn move_camera_left(&mut self) {
@@ -46,6 +96,19 @@ impl Game {
         self.player.on_camera_move(self.camera.perspective(), &self.level);
     }
 
+    /// Puts the player back where they started.
+    pub fn respawn(&mut self) {
+        self.player = Player::new(self.starting_position);
+        let (x, y, z) = self.starting_position;
+        let center = cgmath::Vector3::new(x, y, z + 0.5);
+        self.particles.burst(&particles::SPARKLE, center, particles::SPARKLE_COUNT);
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.res.apply_settings(settings);
+        self.post.apply_settings(settings);
+    }
+
     pub fn zoom_camera(&mut self, diff: f32) {
         self.camera.zoom += diff;
     }
@@ -55,31 +118,200 @@ impl Game {
         let current_tick = Instant::now();
         let delta = current_tick.duration_since(self.last_tick).as_secs_f32();
         self.last_tick = current_tick;
+        if delta > 0.0 {
+            self.fps = if self.fps == 0.0 { 1.0/delta } else { 0.95*self.fps + 0.05/delta };
+        }
+
+        self.reload_shaders();
+        self.step(delta);
+    }
 
+    /// Picks up edited shader files, when they're read from disk.
+    pub fn reload_shaders(&mut self) {
+        let changed = self.shaders.changed();
+        if !changed.is_empty() {
+            self.res.reload_shaders(&self.shaders, &changed);
+            self.post.reload_shaders(&self.shaders, &changed);
+        }
+    }
+
+    /// Advances the game by a fixed `delta` seconds.
+    pub fn step(&mut self, delta: f32) {
+        // jumps happen before the tick, landings during it
+        let jumped_from = cgmath::Vector3::from(self.player.pos);
         self.player.tick(delta, &self.camera, &self.level);
+        let feet = cgmath::Vector3::from(self.player.pos);
+
+        self.particles.tick(delta);
+        for event in self.player.take_events() {
+            match event {
+                PlayerEvent::Jumped => self.particles.burst(&particles::JUMP_DUST, jumped_from, particles::JUMP_DUST_COUNT),
+                PlayerEvent::Landed => self.particles.burst(&particles::LANDING_PUFF, feet, particles::LANDING_PUFF_COUNT),
+                PlayerEvent::Collected => self.particles.burst(&particles::SPARKLE, feet + cgmath::Vector3::new(0.0, 0.0, 0.5), particles::SPARKLE_COUNT),
+            }
+        }
+        let walking = self.player.movement.moving_left() || self.player.movement.moving_right();
+        if walking && self.player.standing_on.is_some() {
+            self.particles.stream(&particles::RUN_DUST, feet, delta, &mut self.run_dust_carry);
+        }
+
+        self.player_animator.tick(delta, &self.player, self.camera.perspective());
 
         self.camera.tick(delta);
         self.res.set_camera_matrices(self.camera.view_matrix(), self.camera.inverse_z_rotation_matrix());
     }
 
     pub fn draw(&self) {
-        self.res.clear(self.level.bg_color);
+        use gfx::debug;
+
+        {
+            let _group = debug::group("shadows");
+            self.res.draw_shadows();
+        }
+
+        if let Err(err) = self.post.begin() {
+            log::error!("could not create post-processing targets, drawing without them: {}", err);
+        }
+
+        {
+            let _group = debug::group("clear");
+            self.res.clear(self.level.background.sky_bottom);
+        }
+        {
+            let _group = debug::group("background");
+            self.res.draw_background();
+        }
+        {
+            let _group = debug::group("platforms");
+            if self.per_platform_draws {
+                self.res.draw_platforms_one_by_one();
+            } else {
+                self.res.draw_platforms();
+            }
+        }
+        {
+            let _group = debug::group("lanterns");
+            for lantern in &self.level.lanterns {
+                self.res.draw_square(lantern.position.into(), 0.4, lantern.color);
+            }
+        }
+        if let Some(platform) = self.player.platform_below(self.camera.perspective(), &self.level) {
+            let _group = debug::group("drop shadow");
+            // smaller and fainter the higher up the player is
+            let fade = 1.0 - (self.player.pos.2 - platform.surface_center.2) / DROP_SHADOW_FADE_HEIGHT;
+            if fade > 0.0 {
+                let base = cgmath::Vector3::new(self.player.pos.0, self.player.pos.1, platform.surface_center.2);
+                self.res.draw_drop_shadow(platform, base, 0.5 + 0.4*fade, 0.5*fade);
+            }
+        }
+        {
+            let _group = debug::group("player");
+            self.res.draw_sprite_frame(self.player.pos.into(), 1.0, &self.player_animator.frame());
+        }
+        {
+            // after everything that could hide the player
+            let _group = debug::group("player silhouette");
+            self.res.draw_silhouette(self.player.pos.into(), 1.0, &self.player_animator.frame());
+        }
+        {
+            let _group = debug::group("particles");
+            self.res.draw_particles(self.particles.particles());
+        }
+
+        {
+            let _group = debug::group("post-processing");
+            self.post.finish();
+        }
 
-        for platform in &self.level.platforms {
-            self.res.draw_platform(
-                platform.surface_center.into(),
-                platform.surface_dim,
-                platform.height,
-                platform.color);
+        // text goes on top, without post-processing
+        let _group = debug::group("hud");
+        if self.show_debug_overlay {
+            debug_overlay::draw(&self.res, &self.level, &self.player, &self.camera);
+            self.res.lines().flush();
+        }
+        let text = self.res.text();
+        if self.show_fps {
+            let (width, _) = text.screen_size();
+            text.queue(&format!("{:.0} fps", self.fps), (width - 8.0, 8.0), &TextStyle {
+                align: Align::Right,
+                ..TextStyle::default()
+            });
         }
+        text.flush();
 
-        self.res.draw_square(self.player.pos.into(), 1.0, (1.0, 1.0, 1.0));
+        {
+            let _group = debug::group("debug draw");
+            debug_draw::flush(&self.res);
+        }
+    }
+}
+
+fn build_atlas(dir: &str, out: &Path) -> Result<(), gfx::Error> {
+    use resources::atlas::{self, Atlas};
+
+    let pngs = atlas::read_pngs(dir).map_err(|error| gfx::Error::Io { path: Some(dir.into()), error })?;
+    let sources = pngs.iter().map(|(name, png)| (&name[..], &png[..])).collect::<Vec<_>>();
+    let atlas = Atlas::from_pngs(&sources)?;
+    atlas.write_cache(&sources, out).map_err(|error| gfx::Error::Io { path: Some(out.into()), error })?;
+
+    log::info!("packed {} textures into {} page(s) at {}", atlas.entries.len(), atlas.pages.len(), out.display());
+    Ok(())
+}
+
+/// Applies the settings file at `path`, if there is one.
+fn load_settings(game: &mut Game, path: &Path) {
+    match Settings::load(path) {
+        Ok(settings) => {
+            game.apply_settings(&settings);
+            log::info!("loaded settings from {}", path.display());
+        },
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => log::info!("no settings at {}, using defaults", path.display()),
+        Err(err) => log::error!("could not load settings: {}", err),
     }
 }
 
 fn main() {
+    // `--gl-debug`, anywhere on the command line, asks for a debug context
+    // and logs driver messages. `--watch-shaders` reads the shaders from
+    // the source tree and reloads them when they're edited. `--per-platform`
+    // draws platforms one by one, to compare against instancing.
+    // `--settings <path>` reads the settings from somewhere else
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let settings_path = match args.iter().position(|arg| arg == "--settings") {
+        Some(i) if i + 1 < args.len() => PathBuf::from(args.drain(i..i + 2).nth(1).unwrap()),
+        _ => settings::default_path(),
+    };
+    let (flags, args): (Vec<String>, Vec<String>) = args.into_iter()
+        .partition(|arg| arg == "--gl-debug" || arg == "--watch-shaders" || arg == "--per-platform");
+    let gl_debug = flags.iter().any(|flag| flag == "--gl-debug");
+    let watch_shaders = flags.iter().any(|flag| flag == "--watch-shaders");
+    let per_platform_draws = flags.iter().any(|flag| flag == "--per-platform");
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(if gl_debug { "debug" } else { "warn,fez_test=info" }))
+        .init();
+
+    // `--bench [side]` loads a large grid of platforms and reports frame times
+    let mut args = args.into_iter();
+    let bench_side = match args.next().as_deref() {
+        Some("--bench") => Some(args.next().and_then(|side| side.parse().ok()).unwrap_or(64)),
+        // `--build-atlas [dir] [out]` packs a directory of pngs into the
+        // cache the game loads at startup, and exits
+        Some("--build-atlas") => {
+            let dir = args.next().unwrap_or_else(|| "src/tex".to_string());
+            let out = args.next().map_or_else(resources::atlas_cache_path, PathBuf::from);
+
+            if let Err(err) = build_atlas(&dir, &out) {
+                log::error!("could not build atlas: {}", err);
+                std::process::exit(1);
+            }
+            return;
+        },
+        _ => None,
+    };
+
     let event_loop = glutin::event_loop::EventLoop::new();
     let context = glutin::ContextBuilder::new()
+        .with_gl_debug_flag(gl_debug)
         .build_windowed(glutin::window::WindowBuilder::new()
             .with_title("fez test")
             .with_inner_size(glutin::dpi::LogicalSize::new(640.0, 480.0))
@@ -93,25 +325,39 @@ fn main() {
     
     // initialize opengl
     gl::load_with(|s| context.get_proc_address(s) as *const _);
-    unsafe {
-        gl::Enable(gl::DEPTH_TEST);
+    if gl_debug && !gfx::debug::enable() {
+        log::warn!("KHR_debug is not supported, no driver messages will be logged");
     }
 
-    let platform_color = (0.38, 0.15, 0.34);
-    let starting_position = (-3.0, -3.0, -5.0);
-
-    let level = Level {
-        bg_color: (0.1, 0.1, 0.1),
-        platforms: vec![
-            Platform::new((0.0, 0.0, -6.0), (8.0, 8.0), 1.0, platform_color),
-            Platform::new((0.0, 0.0, 6.0), (4.0, 4.0), 12.0, platform_color),
-            Platform::new((-5.0, -9.0, -3.0), (2.0, 2.0), 1.0, platform_color),
-            Platform::new((9.0, -5.0, 0.0), (2.0, 2.0), 1.0, platform_color),
-            Platform::new((5.0, 9.0, 3.0), (2.0, 2.0), 1.0, platform_color),
-            Platform::new((-9.0, 5.0, 6.0), (2.0, 2.0), 1.0, platform_color),
-        ],
+    let level = match bench_side {
+        Some(side) => Level::benchmark(side),
+        None => Level::demo(),
     };
-    let mut game = Game::new(level, starting_position);
+    if bench_side.is_some() {
+        let drawn = if per_platform_draws { "one by one" } else { "instanced" };
+        log::info!("benchmark: {} platforms, drawn {}", level.platforms.len(), drawn);
+    }
+    let shaders = if watch_shaders {
+        ShaderSources::from_dir(resources::SHADER_DIR)
+    } else {
+        ShaderSources::embedded()
+    };
+    let mut game = match Game::new(shaders, level, STARTING_POSITION) {
+        Ok(game) => game,
+        Err(err) => {
+            log::error!("could not set up graphics: {}", err);
+            std::process::exit(1);
+        },
+    };
+    game.per_platform_draws = per_platform_draws;
+    load_settings(&mut game, &settings_path);
+
+    let mut frame_count = 0u32;
+    let mut last_report = Instant::now();
+
+    // captures happen right after drawing, before the buffers are swapped
+    let mut screenshot_requested = false;
+    let mut recorder: Option<ClipRecorder> = None;
 
     event_loop.run(move |event, _, control_flow| {
         use glutin::event_loop::ControlFlow;
@@ -146,7 +392,27 @@ fn main() {
                             Z => game.player.press_jump(),
 
                             // reset
-                            R => game.player = Player::new(starting_position),
+                            R => game.respawn(),
+
+                            F1 => game.show_fps = !game.show_fps,
+                            F2 => screenshot_requested = true,
+                            F3 if recorder.is_none() => {
+                                log::info!("recording a {}s clip", capture::CLIP_LENGTH.as_secs_f32());
+                                recorder = Some(ClipRecorder::new());
+                            },
+
+                            F4 => game.show_debug_overlay = !game.show_debug_overlay,
+                            F5 => {
+                                load_settings(&mut game, &settings_path);
+                            },
+                            // toggle post-processing passes
+                            Key1 | Key2 | Key3 | Key4 | Key5 => {
+                                let i = keycode as usize - Key1 as usize;
+                                if let Some(pass) = game.post.passes_mut().get_mut(i) {
+                                    pass.set_enabled(!pass.enabled());
+                                    log::info!("{} pass {}", pass.name(), if pass.enabled() { "on" } else { "off" });
+                                }
+                            },
 
                             _ => (),
                         }
@@ -173,7 +439,32 @@ fn main() {
                 // etc
                 game.draw();
 
+                let size = context.window().inner_size();
+                if screenshot_requested {
+                    screenshot_requested = false;
+                    match capture::screenshot(size.width, size.height) {
+                        Ok(path) => log::info!("saved screenshot to {}", path.display()),
+                        Err(err) => log::error!("could not save screenshot: {}", err),
+                    }
+                }
+                if let Some(clip) = &mut recorder {
+                    if !clip.capture(size.width, size.height) {
+                        recorder.take().unwrap().finish();
+                    }
+                }
+
                 context.swap_buffers().unwrap();
+
+                if bench_side.is_some() {
+                    frame_count += 1;
+
+                    let elapsed = last_report.elapsed().as_secs_f32();
+                    if elapsed >= 1.0 {
+                        log::info!("{} fps ({:.3} ms/frame)", frame_count, 1000.0*elapsed / frame_count as f32);
+                        frame_count = 0;
+                        last_report = Instant::now();
+                    }
+                }
             },
 
             _ => (),
@@ -0,0 +1,245 @@
+//! Short-lived specks of dust and sparkle, simulated on the CPU and drawn
+//! by `Resources::draw_particles` as camera-facing squares.
+
+use cgmath::prelude::*;
+use cgmath::{Deg, Vector3};
+
+type Color = (f32, f32, f32, f32);
+
+/// How particles are spawned and how they change over their life.
+pub struct Emitter {
+    /// Particles per second, for `ParticleSystem::stream`.
+    pub rate: f32,
+    /// Seconds, picked between the two.
+    pub lifetime: (f32, f32),
+    /// Units per second, picked between the two.
+    pub speed: (f32, f32),
+    /// Middle of the cone particles start moving in.
+    pub direction: (f32, f32, f32),
+    /// Angle between the cone's middle and its side: 180 spawns in every
+    /// direction.
+    pub spread: Deg<f32>,
+    /// Downwards acceleration, negative to float up.
+    pub gravity: f32,
+    /// Faded between, from spawning to dying.
+    pub colors: (Color, Color),
+    /// Side of the square, faded between like `colors`.
+    pub sizes: (f32, f32),
+}
+
+/// Kicked up by landing on a platform.
+pub const LANDING_PUFF: Emitter = Emitter {
+    rate: 0.0,
+    lifetime: (0.25, 0.45),
+    speed: (1.5, 3.0),
+    direction: (0.0, 0.0, 1.0),
+    spread: Deg(80.0),
+    gravity: 4.0,
+    colors: ((0.85, 0.8, 0.7, 0.8), (0.85, 0.8, 0.7, 0.0)),
+    sizes: (0.15, 0.3),
+};
+pub const LANDING_PUFF_COUNT: u32 = 12;
+
+/// Pushed off the ground by jumping.
+pub const JUMP_DUST: Emitter = Emitter {
+    rate: 0.0,
+    lifetime: (0.2, 0.35),
+    speed: (0.5, 1.5),
+    direction: (0.0, 0.0, 1.0),
+    spread: Deg(60.0),
+    gravity: 2.0,
+    colors: ((0.85, 0.8, 0.7, 0.6), (0.85, 0.8, 0.7, 0.0)),
+    sizes: (0.1, 0.2),
+};
+pub const JUMP_DUST_COUNT: u32 = 6;
+
+/// Trails the player's feet while they walk.
+pub const RUN_DUST: Emitter = Emitter {
+    rate: 12.0,
+    lifetime: (0.2, 0.3),
+    speed: (0.3, 0.8),
+    direction: (0.0, 0.0, 1.0),
+    spread: Deg(45.0),
+    gravity: 1.0,
+    colors: ((0.85, 0.8, 0.7, 0.5), (0.85, 0.8, 0.7, 0.0)),
+    sizes: (0.1, 0.15),
+};
+
+/// Bursts around the player when they appear or collect something.
+pub const SPARKLE: Emitter = Emitter {
+    rate: 0.0,
+    lifetime: (0.4, 0.8),
+    speed: (1.0, 3.0),
+    direction: (0.0, 0.0, 1.0),
+    spread: Deg(180.0),
+    gravity: -1.0,
+    colors: ((1.0, 0.9, 0.4, 1.0), (1.0, 1.0, 1.0, 0.0)),
+    sizes: (0.2, 0.05),
+};
+pub const SPARKLE_COUNT: u32 = 24;
+
+pub struct Particle {
+    pub position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    gravity: f32,
+    age: f32,
+    lifetime: f32,
+    colors: (Color, Color),
+    sizes: (f32, f32),
+}
+
+impl Particle {
+    // from 0 when spawned to 1 when it dies
+    fn life(&self) -> f32 {
+        self.age / self.lifetime
+    }
+
+    pub fn color(&self) -> Color {
+        let (from, to) = self.colors;
+        let t = self.life();
+        (lerp(from.0, to.0, t), lerp(from.1, to.1, t), lerp(from.2, to.2, t), lerp(from.3, to.3, t))
+    }
+
+    pub fn size(&self) -> f32 {
+        lerp(self.sizes.0, self.sizes.1, self.life())
+    }
+}
+
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    // for xorshift, seeded the same every time so runs can be compared
+    random: u32,
+}
+
+impl ParticleSystem {
+    pub fn new() -> ParticleSystem {
+        ParticleSystem {
+            particles: Vec::new(),
+            random: 0x9e37_79b9,
+        }
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Spawns `count` particles at `position` at once.
+    pub fn burst(&mut self, emitter: &Emitter, position: Vector3<f32>, count: u32) {
+        for _ in 0..count {
+            self.spawn(emitter, position);
+        }
+    }
+
+    /// Spawns particles at `position` at the emitter's rate, for `delta`
+    /// seconds. `carry` keeps the fraction of a particle left over between
+    /// calls, and starts at zero.
+    pub fn stream(&mut self, emitter: &Emitter, position: Vector3<f32>, delta: f32, carry: &mut f32) {
+        *carry += emitter.rate * delta;
+        while *carry >= 1.0 {
+            *carry -= 1.0;
+            self.spawn(emitter, position);
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        for particle in &mut self.particles {
+            particle.velocity.z -= particle.gravity * delta;
+            particle.position += particle.velocity * delta;
+            particle.age += delta;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    fn spawn(&mut self, emitter: &Emitter, position: Vector3<f32>) {
+        // uniform over the cap of the unit sphere within the cone
+        let cos_theta = lerp(1.0, Deg::cos(emitter.spread), self.random());
+        let sin_theta = (1.0 - cos_theta*cos_theta).sqrt();
+        let (sin_phi, cos_phi) = (std::f32::consts::TAU * self.random()).sin_cos();
+
+        let direction = Vector3::from(emitter.direction).normalize();
+        let other = if direction.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+        let side = direction.cross(other).normalize();
+        let side2 = direction.cross(side);
+        let heading = cos_theta*direction + sin_theta*(cos_phi*side + sin_phi*side2);
+
+        let speed = lerp(emitter.speed.0, emitter.speed.1, self.random());
+        let lifetime = lerp(emitter.lifetime.0, emitter.lifetime.1, self.random());
+        self.particles.push(Particle {
+            position,
+            velocity: speed*heading,
+            gravity: emitter.gravity,
+            age: 0.0,
+            lifetime,
+            colors: emitter.colors,
+            sizes: emitter.sizes,
+        });
+    }
+
+    // between 0 and 1
+    fn random(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from)*t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // everything fixed, so particles are predictable
+    const STILL: Emitter = Emitter {
+        rate: 10.0,
+        lifetime: (2.0, 2.0),
+        speed: (0.0, 0.0),
+        direction: (0.0, 0.0, 1.0),
+        spread: Deg(0.0),
+        gravity: 0.0,
+        colors: ((0.0, 0.0, 0.0, 1.0), (1.0, 1.0, 1.0, 0.0)),
+        sizes: (1.0, 3.0),
+    };
+
+    #[test]
+    fn particles_expire() {
+        let mut system = ParticleSystem::new();
+        system.burst(&STILL, Vector3::zero(), 5);
+        assert_eq!(system.particles().len(), 5);
+
+        system.tick(1.5);
+        assert_eq!(system.particles().len(), 5);
+        system.tick(0.5);
+        assert!(system.particles().is_empty());
+    }
+
+    #[test]
+    fn streams_carry_fractions_over() {
+        let mut system = ParticleSystem::new();
+        let mut carry = 0.0;
+
+        system.stream(&STILL, Vector3::zero(), 0.25, &mut carry);
+        assert_eq!((system.particles().len(), carry), (2, 0.5));
+        system.stream(&STILL, Vector3::zero(), 0.25, &mut carry);
+        assert_eq!((system.particles().len(), carry), (5, 0.0));
+        system.stream(&STILL, Vector3::zero(), 0.05, &mut carry);
+        assert_eq!(system.particles().len(), 5);
+    }
+
+    #[test]
+    fn color_and_size_fade_over_life() {
+        let mut system = ParticleSystem::new();
+        system.burst(&STILL, Vector3::zero(), 1);
+
+        let particle = &system.particles()[0];
+        assert_eq!((particle.color(), particle.size()), ((0.0, 0.0, 0.0, 1.0), 1.0));
+
+        system.tick(0.5);
+        let particle = &system.particles()[0];
+        assert_eq!((particle.color(), particle.size()), ((0.25, 0.25, 0.25, 0.75), 1.5));
+        assert_eq!(particle.position, Vector3::zero());
+    }
+}
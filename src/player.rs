@@ -63,6 +63,14 @@ pub const JUMP_GRAVITY: f32 = 25.0;
 pub const DEFAULT_GRAVITY: f32 = 50.0;
 pub const MAX_FALL_VEL: f32 = 24.0;
 
+/// Something that happened to the player, for effects to react to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlayerEvent {
+    Jumped,
+    Landed,
+    Collected,
+}
+
 pub struct Player {
     pub pos: (f32, f32, f32),
 
@@ -71,6 +79,9 @@ pub struct Player {
 
     pub standing_on: Option<usize>,
     pub behind_wall: bool,
+
+    // since the last take_events
+    events: Vec<PlayerEvent>,
 }
 
 use super::{Camera, Perspective, Level, Platform};
@@ -83,6 +94,7 @@ impl Player {
             standing_on: None,
             movement: Movement::empty(),
             behind_wall: false,
+            events: Vec::new(),
         }
     }
 
@@ -95,6 +107,7 @@ impl Player {
                 self.z_vel = JUMP_VEL;
                 self.standing_on = None;
                 self.movement.insert(Movement::JUMPING);
+                self.events.push(PlayerEvent::Jumped);
             }
         }
     }
@@ -102,6 +115,18 @@ impl Player {
         self.movement.remove(Movement::JUMPING);
     }
 
+    /// Picks something up where the player stands.
+    // nothing calls this until levels have collectibles
+    #[allow(dead_code)]
+    pub fn collect(&mut self) {
+        self.events.push(PlayerEvent::Collected);
+    }
+
+    /// What happened since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<PlayerEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn on_camera_move(&mut self, perspective: Perspective, level: &Level) {
         use Perspective::*;
 
@@ -135,28 +160,41 @@ impl Player {
         }
     }
 
-    pub fn tick(&mut self, delta: f32, camera: &Camera, level: &Level) {
-        use Perspective::*;
+    /// Whether the player counts as climbing. There is nothing to climb yet,
+    /// so rising behind a wall stands in for it.
+    pub fn climbing(&self) -> bool {
+        self.behind_wall && self.standing_on.is_none() && self.z_vel > 0.0
+    }
 
-        let mut new_pos = self.pos;
-        if self.movement.moving_left() {
-            match camera.perspective() {
-                S => new_pos.0 -= MOVE_VEL*delta,
-                N => new_pos.0 += MOVE_VEL*delta,
+    /// Current velocity in world space, walking as seen from `perspective`.
+    pub fn velocity(&self, perspective: Perspective) -> (f32, f32, f32) {
+        use Perspective::*;
 
-                E => new_pos.1 -= MOVE_VEL*delta,
-                W => new_pos.1 += MOVE_VEL*delta,
-            };
+        let walk = if self.movement.moving_left() {
+            -MOVE_VEL
         } else if self.movement.moving_right() {
-            match camera.perspective() {
-                S => new_pos.0 += MOVE_VEL*delta,
-                N => new_pos.0 -= MOVE_VEL*delta,
+            MOVE_VEL
+        } else {
+            0.0
+        };
 
-                E => new_pos.1 += MOVE_VEL*delta,
-                W => new_pos.1 -= MOVE_VEL*delta,
-            };
+        match perspective {
+            S => (walk, 0.0, self.z_vel),
+            N => (-walk, 0.0, self.z_vel),
+
+            E => (0.0, walk, self.z_vel),
+            W => (0.0, -walk, self.z_vel),
         }
-        
+    }
+
+    pub fn tick(&mut self, delta: f32, camera: &Camera, level: &Level) {
+        use Perspective::*;
+
+        let was_standing = self.standing_on.is_some();
+
+        let velocity = self.velocity(camera.perspective());
+        let new_pos = (self.pos.0 + velocity.0*delta, self.pos.1 + velocity.1*delta, self.pos.2);
+
         // TODO: check against x/y collision
         self.pos = new_pos;
 
@@ -195,6 +233,9 @@ impl Player {
         if self.z_vel < 0.0 {
             self.standing_on = None;
         }
+        if !was_standing && self.standing_on.is_some() {
+            self.events.push(PlayerEvent::Landed);
+        }
 
         // ensure we are not behind a wall
         // theres possibly a better way but whatevs
@@ -218,6 +259,20 @@ impl Player {
         }
     }
 
+    /// The platform the player would land on by falling straight down in
+    /// `perspective`, which is the one they are standing on, if any.
+    pub fn platform_below<'a>(&self, perspective: Perspective, level: &'a Level) -> Option<&'a Platform> {
+        use Perspective::*;
+
+        // the same test as landing, down to any depth
+        level.platforms.iter()
+            .filter(|platform| match perspective {
+                S | N => platform.intersection_x(self.pos.0, 1.0, f32::NEG_INFINITY, self.pos.2),
+                W | E => platform.intersection_y(self.pos.1, 1.0, f32::NEG_INFINITY, self.pos.2),
+            })
+            .max_by(|a, b| a.surface_center.2.total_cmp(&b.surface_center.2))
+    }
+
     fn behind_wall(&self, perspective: Perspective, platform: &Platform) -> bool {
         use Perspective::*;
 
@@ -1,116 +1,254 @@
 use gl::types::*;
+
 use cgmath::prelude::*;
+use cgmath::{Matrix4, Vector3, Vector4};
 
-use cgmath::{Matrix4, Vector3};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
 
 mod vertex_data;
+use vertex_data::{EdgeVertex, PlatformVertex, SquareVertex};
 mod shader_sources;
-
-pub static TEST_PNG: &[u8] = include_bytes!("tex/test.png");
+pub use shader_sources::{ShaderSources, SHADER_DIR};
+pub mod atlas;
+use atlas::{Atlas, AtlasEntry};
+mod image;
+pub use image::Image;
+mod lighting;
+use lighting::{LightingUniforms, Lights, SHADOW_MAP_UNIT};
+pub mod postprocess;
+pub mod font;
+mod text;
+pub use text::{TextRenderer, TextStyle};
+mod lines;
+pub use lines::LineRenderer;
+mod particles;
+use particles::ParticleRenderer;
+mod background;
+use background::BackgroundRenderer;
+
+/// Pngs packed into the texture atlas, named after their files in
+/// `src/tex`. Listed by `build.rs`.
+pub const TEXTURES: &[(&str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/textures.rs"));
+
+// platforms are the instanced variant of the solid program
+const SOLID_STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "solid.vert"), (ShaderType::Fragment, "solid.frag")];
+const PLATFORM_DEFINES: &[(&str, &str)] = &[("INSTANCED", "1")];
+// platforms again, seen from the sun
+const SHADOW_STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "solid.vert"), (ShaderType::Fragment, "shadow.frag")];
+// the player's drop shadow, on the square mesh
+const BLOB_STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "solid.vert"), (ShaderType::Fragment, "blob.frag")];
+// sprites where something hides them
+const SILHOUETTE_STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "solid.vert"), (ShaderType::Fragment, "silhouette.frag")];
+
+/// Side of the square shadow map, in texels.
+const SHADOW_MAP_SIZE: u32 = 2048;
 
 use crate::gfx::*;
 use crate::c_str;
+use crate::level::{Background, Lantern, Lighting, Platform};
+use crate::particles::Particle;
+use crate::settings::Settings;
+
+/// Where the packed atlas is cached between runs: under `$XDG_CACHE_HOME`,
+/// `~/.cache` or else the temporary directory.
+pub fn atlas_cache_path() -> PathBuf {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+
+    cache_dir.join("fez-test").join("textures.atlas")
+}
+
+// for level data with more of something than the shaders have room for
+fn warn_over_limit(what: &str, count: usize, max: usize) {
+    if count > max {
+        log::warn!("{} {}, only the first {} are used", count, what, max);
+    }
+}
+
+/// A cell of a sprite sheet laid out as a uniform grid.
+pub struct SpriteFrame {
+    pub texture: &'static str,
+    pub grid: (u32, u32),
+    pub cell: (u32, u32),
+    pub flip_x: bool,
+}
 
 #[allow(dead_code)] // so "unused" fields dont trigger the warn (they impl Drop)
 pub struct Resources {
-    platform_faces: BufferObject,
-    platform_faces_vao: VertexArrayObject,
+    platform_faces: Mesh<PlatformVertex>,
+    platform_edges: Mesh<EdgeVertex>,
+    platform_solids: Mesh<PlatformVertex>,
 
-    platform_edges: BufferObject,
-    platform_edges_vao: VertexArrayObject,
+    // per-instance data, rebuilt by set_platforms
+    platform_transforms: Buffer<Matrix4<f32>>,
+    platform_colors: Buffer<[GLfloat; 3]>,
+    platform_uv_rects: Buffer<[GLfloat; 4]>,
+    platform_shininess: Buffer<GLfloat>,
+    // instances are sorted by atlas page, so each of these is a contiguous run
+    platform_batches: Vec<(Option<usize>, Range<usize>)>,
+    platform_instance_attribs: InstanceAttribs,
 
-    square_faces: BufferObject,
-    square_faces_vao: VertexArrayObject,
+    square_faces: Mesh<SquareVertex>,
 
     shader_program: ShaderProgram,
 
-    unif_model: GLint,
-    unif_view: GLint,
-    unif_proj: GLint,
-    unif_color: GLint,
-    unif_apply_diffuse: GLint,
-
-    test_texture: Texture,
+    unif_model: Uniform<Matrix4<f32>>,
+    unif_view: Uniform<Matrix4<f32>>,
+    unif_proj: Uniform<Matrix4<f32>>,
+    unif_color: Uniform<Vector3<f32>>,
+    unif_apply_diffuse: Uniform<bool>,
+    unif_textured: Uniform<bool>,
+    unif_uv_rect: Uniform<Vector4<f32>>,
+    unif_shininess: Uniform<f32>,
+    unif_lighting: LightingUniforms,
+
+    platform_program: ShaderProgram,
+
+    unif_platform_view: Uniform<Matrix4<f32>>,
+    unif_platform_proj: Uniform<Matrix4<f32>>,
+    unif_platform_use_instance_color: Uniform<bool>,
+    unif_platform_color: Uniform<Vector3<f32>>,
+    unif_platform_apply_diffuse: Uniform<bool>,
+    unif_platform_textured: Uniform<bool>,
+    unif_platform_lighting: LightingUniforms,
+
+    shadow_target: DepthTarget,
+    shadow_program: ShaderProgram,
+
+    unif_shadow_view: Uniform<Matrix4<f32>>,
+    unif_shadow_proj: Uniform<Matrix4<f32>>,
+
+    blob_program: ShaderProgram,
+
+    unif_blob_model: Uniform<Matrix4<f32>>,
+    unif_blob_view: Uniform<Matrix4<f32>>,
+    unif_blob_proj: Uniform<Matrix4<f32>>,
+    unif_blob_opacity: Uniform<f32>,
+
+    silhouette_program: ShaderProgram,
+
+    unif_silhouette_model: Uniform<Matrix4<f32>>,
+    unif_silhouette_view: Uniform<Matrix4<f32>>,
+    unif_silhouette_proj: Uniform<Matrix4<f32>>,
+    unif_silhouette_uv_rect: Uniform<Vector4<f32>>,
+    unif_silhouette_tint: Uniform<Vector3<f32>>,
+    unif_silhouette_opacity: Uniform<f32>,
+
+    silhouette: Silhouette,
+
+    text: TextRenderer,
+    lines: LineRenderer,
+    particles: ParticleRenderer,
+    background: BackgroundRenderer,
+
+    view: Matrix4<f32>,
+    proj: Matrix4<f32>,
+    lights: Lights,
+    // towards the camera, in world space
+    eye_direction: Vector3<f32>,
+    // corners of the box around every platform, which the shadow map covers
+    platform_bounds: (Vector3<f32>, Vector3<f32>),
+
+    atlas_pages: Vec<(Texture, (u32, u32))>,
+    atlas_entries: HashMap<String, AtlasEntry>,
 
     camera_inverse_rotation: Matrix4<f32>,
 }
 
 impl Resources {
-    pub fn new() -> Resources {
-        // initialize all opengl data
-        let vertex_shader = Shader::compile(ShaderType::Vertex, shader_sources::SOLID_VERTEX_SHADER).unwrap();
-        let fragment_shader = Shader::compile(ShaderType::Fragment, shader_sources::SOLID_FRAGMENT_SHADER).unwrap();
-
-        let shader_program = ShaderProgram::new();
-        shader_program.attach(&vertex_shader);
-        shader_program.attach(&fragment_shader);
-        shader_program.link().unwrap();
-        shader_program.use_();
-
-        let pos_attrib = unsafe {
-            gl::GetAttribLocation(shader_program.name(), c_str!("inPosition").as_ptr()) as u32
-        };
-        let normal_attrib = unsafe {
-            gl::GetAttribLocation(shader_program.name(), c_str!("inNormal").as_ptr()) as u32
-        };
-
-        use std::mem::size_of_val;
-
-        let platform_faces = BufferObject::new();
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, platform_faces.name());
-            gl::BufferData(gl::ARRAY_BUFFER, size_of_val(vertex_data::PLATFORM_FACES) as GLsizeiptr, vertex_data::PLATFORM_FACES.as_ptr() as *const _, gl::STATIC_DRAW);
-        }
-
-        let platform_faces_vao = VertexArrayObject::new();
-        platform_faces_vao.bind();
-        unsafe {
-            gl::VertexAttribPointer(pos_attrib, 3, gl::FLOAT, gl::FALSE, size_of_val(&vertex_data::PLATFORM_FACES[0]) as GLint, std::ptr::null_mut());
-            gl::EnableVertexAttribArray(pos_attrib);
-
-            gl::VertexAttribPointer(normal_attrib, 3, gl::FLOAT, gl::FALSE, size_of_val(&vertex_data::PLATFORM_FACES[0]) as GLint, std::ptr::null_mut::<GLfloat>().offset(3) as *mut _);
-            gl::EnableVertexAttribArray(normal_attrib);
-        }
-
-        let platform_edges = BufferObject::new();
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, platform_edges.name());
-            gl::BufferData(gl::ARRAY_BUFFER, size_of_val(vertex_data::PLATFORM_EDGES) as GLsizeiptr, vertex_data::PLATFORM_EDGES.as_ptr() as *const _, gl::STATIC_DRAW);
-        }
-
-        let platform_edges_vao = VertexArrayObject::new();
-        platform_edges_vao.bind();
+    pub fn new(shaders: &ShaderSources) -> Result<Resources, Error> {
         unsafe {
-            gl::VertexAttribPointer(pos_attrib, 3, gl::FLOAT, gl::FALSE, size_of_val(&vertex_data::PLATFORM_EDGES[0]) as GLint, std::ptr::null_mut());
-            gl::EnableVertexAttribArray(pos_attrib);
+            gl::Enable(gl::DEPTH_TEST);
         }
 
-        let square_faces = unsafe {
-            let vbo = BufferObject::new();
-
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.name());
-            gl::BufferData(gl::ARRAY_BUFFER, size_of_val(vertex_data::SQUARE_FACES) as GLsizeiptr, vertex_data::SQUARE_FACES.as_ptr() as *const _, gl::STATIC_DRAW);
-
-            vbo
+        // initialize all opengl data
+        let shader_program = shaders.build_program("solid", SOLID_STAGES, &[])?;
+        let platform_program = shaders.build_program("platform", SOLID_STAGES, PLATFORM_DEFINES)?;
+        // these can draw the meshes of the programs they're built like
+        let shadow_program = shaders.build_program_like(&platform_program, "shadow", SHADOW_STAGES, PLATFORM_DEFINES)?;
+        let blob_program = shaders.build_program_like(&shader_program, "blob", BLOB_STAGES, &[])?;
+        let silhouette_program = shaders.build_program_like(&shader_program, "silhouette", SILHOUETTE_STAGES, &[])?;
+
+        let platform_transforms = Buffer::new(BufferTarget::Array, BufferUsage::Dynamic);
+        let platform_colors = Buffer::new(BufferTarget::Array, BufferUsage::Dynamic);
+        let platform_uv_rects = Buffer::new(BufferTarget::Array, BufferUsage::Dynamic);
+        let platform_shininess = Buffer::new(BufferTarget::Array, BufferUsage::Dynamic);
+        let platform_instance_attribs = InstanceAttribs {
+            model: platform_program.attrib_location(c_str!("inModel")),
+            color: platform_program.attrib_location(c_str!("inColor")),
+            uv_rect: platform_program.attrib_location(c_str!("inUvRect")),
+            shininess: platform_program.attrib_location(c_str!("inShininess")),
         };
 
-        let square_faces_vao = VertexArrayObject::new();
-        square_faces_vao.bind();
+        let platform_faces = Mesh::indexed(&platform_program, gl::TRIANGLES, vertex_data::PLATFORM_VERTICES, vertex_data::PLATFORM_FACE_INDICES)?;
+        platform_instance_attribs.bind(&platform_transforms, &platform_colors, &platform_uv_rects, &platform_shininess, 0);
+
+        let platform_edges = Mesh::indexed(&platform_program, gl::LINES, vertex_data::PLATFORM_CORNERS, vertex_data::PLATFORM_EDGE_INDICES)?;
+        platform_instance_attribs.bind(&platform_transforms, &platform_colors, &platform_uv_rects, &platform_shininess, 0);
+
+        // drawn by the shadow program, which has no use for normals
+        let platform_solids = Mesh::indexed(&platform_program, gl::TRIANGLES, vertex_data::PLATFORM_VERTICES, vertex_data::PLATFORM_SOLID_INDICES)?;
+        platform_instance_attribs.bind(&platform_transforms, &platform_colors, &platform_uv_rects, &platform_shininess, 0);
+
+        let square_faces = Mesh::indexed(&shader_program, gl::TRIANGLES, vertex_data::SQUARE_VERTICES, vertex_data::SQUARE_INDICES)?;
+
+        platform_faces.set_label("platform faces");
+        platform_edges.set_label("platform edges");
+        platform_solids.set_label("platform solids");
+        square_faces.set_label("square");
+        platform_transforms.set_label("platform transforms");
+        platform_colors.set_label("platform colors");
+        platform_uv_rects.set_label("platform uv rects");
+        platform_shininess.set_label("platform shininess");
+
+        let unif_model = shader_program.uniform(c_str!("model"))?;
+        let unif_view = shader_program.uniform(c_str!("view"))?;
+        let unif_proj = shader_program.uniform(c_str!("proj"))?;
+        let unif_color = shader_program.uniform(c_str!("color"))?;
+        let unif_apply_diffuse = shader_program.uniform(c_str!("apply_diffuse"))?;
+        let unif_textured = shader_program.uniform(c_str!("textured"))?;
+        let unif_uv_rect = shader_program.uniform(c_str!("uv_rect"))?;
+        let unif_shininess = shader_program.uniform(c_str!("shininess"))?;
+        let unif_lighting = LightingUniforms::new(&shader_program)?;
+
+        let unif_platform_view = platform_program.uniform(c_str!("view"))?;
+        let unif_platform_proj = platform_program.uniform(c_str!("proj"))?;
+        let unif_platform_use_instance_color = platform_program.uniform(c_str!("use_instance_color"))?;
+        let unif_platform_color = platform_program.uniform(c_str!("color"))?;
+        let unif_platform_apply_diffuse = platform_program.uniform(c_str!("apply_diffuse"))?;
+        let unif_platform_textured = platform_program.uniform(c_str!("textured"))?;
+        let unif_platform_lighting = LightingUniforms::new(&platform_program)?;
+
+        let unif_shadow_view = shadow_program.uniform(c_str!("view"))?;
+        let unif_shadow_proj = shadow_program.uniform(c_str!("proj"))?;
+
+        let unif_blob_model = blob_program.uniform(c_str!("model"))?;
+        let unif_blob_view = blob_program.uniform(c_str!("view"))?;
+        let unif_blob_proj = blob_program.uniform(c_str!("proj"))?;
+        let unif_blob_opacity = blob_program.uniform(c_str!("opacity"))?;
+
+        let unif_silhouette_model = silhouette_program.uniform(c_str!("model"))?;
+        let unif_silhouette_view = silhouette_program.uniform(c_str!("view"))?;
+        let unif_silhouette_proj = silhouette_program.uniform(c_str!("proj"))?;
+        let unif_silhouette_uv_rect = silhouette_program.uniform(c_str!("uv_rect"))?;
+        let unif_silhouette_tint = silhouette_program.uniform(c_str!("tint"))?;
+        let unif_silhouette_opacity = silhouette_program.uniform(c_str!("opacity"))?;
+
+        let shadow_target = DepthTarget::new(SHADOW_MAP_SIZE, SHADOW_MAP_SIZE)?;
+        shadow_target.set_label("shadow map");
         unsafe {
-            gl::VertexAttribPointer(pos_attrib, 3, gl::FLOAT, gl::FALSE, size_of_val(&vertex_data::SQUARE_FACES[0]) as GLint, std::ptr::null_mut());
-            gl::EnableVertexAttribArray(pos_attrib);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D, shadow_target.depth_texture().name());
+            gl::ActiveTexture(gl::TEXTURE0);
         }
 
-        let unif_model = shader_program.get_uniform_location(c_str!("model"));
-        let unif_view = shader_program.get_uniform_location(c_str!("view"));
-        let unif_proj = shader_program.get_uniform_location(c_str!("proj"));
-        let unif_color = shader_program.get_uniform_location(c_str!("color"));
-        let unif_apply_diffuse = shader_program.get_uniform_location(c_str!("apply_diffuse"));
-
         const ASPECT_RATIO: f32 = 640.0 / 480.0;
 
         // matrix transformations
-        use cgmath::prelude::*;
         //let proj = cgmath::perspective(cgmath::Deg(45.0), ASPECT_RATIO, 1.0, 10.0);
         // orthogonal (w fixed aspect ratio)
         let proj =
@@ -121,34 +259,45 @@ impl Resources {
                 -8.0, 8.0,
                 -100.0, 100.0,
             );
-        unsafe {
-            gl::UniformMatrix4fv(unif_proj, 1, gl::FALSE, proj.as_ptr());
-        }
-
-        // decode test texture
-        let test_texture = Texture::load_from_png(std::io::Cursor::new(TEST_PNG));
-        unsafe {
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-        }
 
-        unsafe {
-            assert_eq!(gl::GetError(), 0);
-        }
-
-        Resources {
+        let text = TextRenderer::new(shaders)?;
+        let lines = LineRenderer::new(shaders)?;
+        let particles = ParticleRenderer::new(shaders)?;
+        let background = BackgroundRenderer::new(shaders)?;
+
+        // pack textures, referenced by name from level data
+        let atlas = Atlas::load_cached(TEXTURES, atlas_cache_path())?;
+        let atlas_pages = atlas.pages.iter().enumerate()
+            .map(|(i, page)| {
+                let texture = Texture::from_rgba8(page.width, page.height, &page.pixels, &TextureOptions {
+                    // wrapping happens in the shader, per atlas entry
+                    wrap: Wrap::ClampToEdge,
+                    mag_filter: Filter::Nearest,
+                    // the padding is too thin for mipmaps
+                    mipmaps: false,
+                    ..TextureOptions::default()
+                });
+                texture.set_label(&format!("atlas page {}", i));
+
+                (texture, (page.width, page.height))
+            })
+            .collect();
+
+        check_gl_error("while loading resources")?;
+
+        Ok(Resources {
             platform_faces,
-            platform_faces_vao,
-
             platform_edges,
-            platform_edges_vao,
+            platform_solids,
+
+            platform_transforms,
+            platform_colors,
+            platform_uv_rects,
+            platform_shininess,
+            platform_batches: Vec::new(),
+            platform_instance_attribs,
 
             square_faces,
-            square_faces_vao,
 
             shader_program,
 
@@ -157,11 +306,61 @@ impl Resources {
             unif_proj,
             unif_color,
             unif_apply_diffuse,
+            unif_textured,
+            unif_uv_rect,
+            unif_shininess,
+            unif_lighting,
+
+            platform_program,
+
+            unif_platform_view,
+            unif_platform_proj,
+            unif_platform_use_instance_color,
+            unif_platform_color,
+            unif_platform_apply_diffuse,
+            unif_platform_textured,
+            unif_platform_lighting,
+
+            shadow_target,
+            shadow_program,
+
+            unif_shadow_view,
+            unif_shadow_proj,
+
+            blob_program,
+
+            unif_blob_model,
+            unif_blob_view,
+            unif_blob_proj,
+            unif_blob_opacity,
+
+            silhouette_program,
+
+            unif_silhouette_model,
+            unif_silhouette_view,
+            unif_silhouette_proj,
+            unif_silhouette_uv_rect,
+            unif_silhouette_tint,
+            unif_silhouette_opacity,
+
+            silhouette: Silhouette::default(),
 
-            test_texture,
+            text,
+            lines,
+            particles,
+            background,
+
+            view: Matrix4::identity(),
+            proj,
+            lights: Lights::new(&Lighting::default(), &[]),
+            eye_direction: Vector3::unit_z(),
+            platform_bounds: (Vector3::zero(), Vector3::zero()),
+
+            atlas_pages,
+            atlas_entries: atlas.entries,
 
             camera_inverse_rotation: Matrix4::identity(),
-        }
+        })
     }
 
     pub fn clear(&self, color: (f32, f32, f32)) {
@@ -171,52 +370,489 @@ impl Resources {
         }
     }
 
+    /// Runs `draw` against a `width` by `height` off-screen render target
+    /// and reads back the result. The previous framebuffer and viewport are
+    /// restored afterwards.
+    pub fn render_offscreen<F: FnOnce(&Resources)>(&self, width: u32, height: u32, draw: F) -> Result<Image, Error> {
+        let mut viewport = [0; 4];
+        let mut previous_framebuffer = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        }
+
+        let target = RenderTarget::new(width, height, 1, true)?;
+        target.set_label("offscreen");
+        target.bind();
+
+        draw(self);
+        target.resolve();
+        target.bind_read();
+        let image = Image::read_framebuffer(width, height);
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+
+        Ok(image)
+    }
+
+    /// Recompiles the programs using any of the `changed` sources, see
+    /// `ShaderSources::reload`.
+    pub fn reload_shaders(&self, shaders: &ShaderSources, changed: &[String]) {
+        shaders.reload(&self.shader_program, "solid", SOLID_STAGES, &[], changed);
+        shaders.reload(&self.platform_program, "platform", SOLID_STAGES, PLATFORM_DEFINES, changed);
+        shaders.reload(&self.shadow_program, "shadow", SHADOW_STAGES, PLATFORM_DEFINES, changed);
+        shaders.reload(&self.blob_program, "blob", BLOB_STAGES, &[], changed);
+        shaders.reload(&self.silhouette_program, "silhouette", SILHOUETTE_STAGES, &[], changed);
+        self.text.reload_shaders(shaders, changed);
+        self.lines.reload_shaders(shaders, changed);
+        self.particles.reload_shaders(shaders, changed);
+        self.background.reload_shaders(shaders, changed);
+    }
+
+    /// For queueing screen-space text, drawn by `TextRenderer::flush`.
+    pub fn text(&self) -> &TextRenderer {
+        &self.text
+    }
+
+    /// For queueing world-space lines, drawn by `LineRenderer::flush`.
+    pub fn lines(&self) -> &LineRenderer {
+        &self.lines
+    }
+
+    /// Where `position` ends up on screen, in pixels from the top left
+    /// corner of the viewport.
+    pub fn world_to_screen(&self, position: Vector3<f32>) -> (f32, f32) {
+        let (width, height) = self.text.screen_size();
+        let clip = self.proj * self.view * position.extend(1.0);
+
+        (0.5*(clip.x/clip.w + 1.0)*width, 0.5*(1.0 - clip.y/clip.w)*height)
+    }
+
     pub fn set_camera_matrices(&mut self, view: Matrix4<f32>, inverse_rotation: Matrix4<f32>) {
         self.camera_inverse_rotation = inverse_rotation;
+        self.view = view;
+        // the view's z axis, back in world space
+        self.eye_direction = view.invert()
+            .map_or(Vector3::unit_z(), |inverse| (inverse * Vector4::unit_z()).truncate().normalize());
+
+        // the projection never changes, but reloaded programs lose it
+        let program = self.shader_program.bind();
+        program.set(&self.unif_view, view);
+        program.set(&self.unif_proj, self.proj);
+        let program = self.platform_program.bind();
+        program.set(&self.unif_platform_view, view);
+        program.set(&self.unif_platform_proj, self.proj);
+        let program = self.blob_program.bind();
+        program.set(&self.unif_blob_view, view);
+        program.set(&self.unif_blob_proj, self.proj);
+        let program = self.silhouette_program.bind();
+        program.set(&self.unif_silhouette_view, view);
+        program.set(&self.unif_silhouette_proj, self.proj);
+        self.lines.set_camera_matrices(view, self.proj);
+        self.particles.set_camera_matrices(view, self.proj, inverse_rotation);
+    }
+
+    /// Reads the `[silhouette]` section. Missing keys keep their values.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let silhouette = &mut self.silhouette;
+        if let Some(enabled) = settings.get_parsed("silhouette", "enabled") {
+            silhouette.enabled = enabled;
+        }
+        for (key, value) in [("tint_r", &mut silhouette.tint.x), ("tint_g", &mut silhouette.tint.y), ("tint_b", &mut silhouette.tint.z), ("opacity", &mut silhouette.opacity)] {
+            if let Some(parsed) = settings.get_parsed("silhouette", key) {
+                *value = parsed;
+            }
+        }
+    }
+
+    /// Draws `background` behind the level from now on. Only the first
+    /// `background::MAX_LAYERS` layers are kept.
+    pub fn set_background(&mut self, background: &Background) {
+        self.background.set_background(background);
+    }
+
+    /// Lights platforms from now on. Only the first
+    /// `lighting::MAX_LANTERNS` lanterns are taken into account.
+    pub fn set_lighting(&mut self, lighting: &Lighting, lanterns: &[Lantern]) {
+        self.lights = Lights::new(lighting, lanterns);
+    }
+
+    /// Rebuilds the per-instance platform buffers. Only needs to be called
+    /// when the level changes.
+    pub fn set_platforms(&mut self, platforms: &[Platform]) {
+        let atlas_entries = &self.atlas_entries;
+        let entry = |platform: &Platform| {
+            let texture = platform.texture.as_ref()?;
+            let entry = atlas_entries.get(texture);
+            if entry.is_none() {
+                log::warn!("unknown texture {:?}, drawing platform untextured", texture);
+            }
+
+            entry
+        };
+
+        // group platforms by atlas page, untextured ones first
+        let mut platforms: Vec<(&Platform, Option<&AtlasEntry>)> = platforms.iter()
+            .map(|platform| (platform, entry(platform)))
+            .collect();
+        platforms.sort_by_key(|(_, entry)| entry.map(|entry| entry.page));
+
+        self.platform_batches.clear();
+        for (i, (_, entry)) in platforms.iter().enumerate() {
+            let page = entry.map(|entry| entry.page);
+            match self.platform_batches.last_mut() {
+                Some((last, range)) if *last == page => range.end = i + 1,
+                _ => self.platform_batches.push((page, i..i + 1)),
+            }
+        }
+
+        self.platform_bounds = platforms.iter()
+            .map(|(platform, _)| {
+                let half = Vector3::new(platform.surface_dim.0/2.0, platform.surface_dim.1/2.0, 0.0);
+                let top = Vector3::from(platform.surface_center);
+                (top - half - Vector3::new(0.0, 0.0, platform.height), top + half)
+            })
+            .fold(None, |bounds: Option<(Vector3<f32>, Vector3<f32>)>, (min, max)| Some(match bounds {
+                Some((low, high)) => (
+                    Vector3::new(low.x.min(min.x), low.y.min(min.y), low.z.min(min.z)),
+                    Vector3::new(high.x.max(max.x), high.y.max(max.y), high.z.max(max.z)),
+                ),
+                None => (min, max),
+            }))
+            .unwrap_or((Vector3::zero(), Vector3::zero()));
+
+        let transforms: Vec<Matrix4<f32>> = platforms.iter()
+            .map(|(platform, _)| {
+                let scale = Matrix4::from_nonuniform_scale(platform.surface_dim.0, platform.surface_dim.1, platform.height);
+                let translate = Matrix4::from_translation(Vector3::from(platform.surface_center) - Vector3::new(0.0, 0.0, platform.height/2.0));
+
+                translate*scale
+            })
+            .collect();
+        let colors: Vec<[GLfloat; 3]> = platforms.iter()
+            .map(|(platform, _)| platform.color.into())
+            .collect();
+        let uv_rects: Vec<[GLfloat; 4]> = platforms.iter()
+            .map(|(_, entry)| match entry {
+                Some(entry) => entry.uv_rect(self.atlas_pages[entry.page].1),
+                None => [0.0; 4],
+            })
+            .collect();
+        let shininess: Vec<GLfloat> = platforms.iter()
+            .map(|(platform, _)| platform.shininess)
+            .collect();
+
+        self.platform_transforms.set_data(&transforms);
+        self.platform_colors.set_data(&colors);
+        self.platform_uv_rects.set_data(&uv_rects);
+        self.platform_shininess.set_data(&shininess);
+    }
+
+    /// Renders the platforms from the sun into the shadow map, which
+    /// `draw_platforms` then reads. The previous framebuffer and viewport
+    /// are restored afterwards.
+    pub fn draw_shadows(&self) {
+        let mut viewport = [0; 4];
+        let mut previous_framebuffer = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        }
+
+        self.shadow_target.bind();
+        unsafe {
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+            // slopes facing away from the sun would shadow themselves
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(2.0, 4.0);
+        }
+
+        let program = self.shadow_program.bind();
+        program.set(&self.unif_shadow_view, Matrix4::identity());
+        program.set(&self.unif_shadow_proj, self.light_matrix());
+
+        self.platform_solids.bind();
+        self.platform_instance_attribs.bind(&self.platform_transforms, &self.platform_colors, &self.platform_uv_rects, &self.platform_shininess, 0);
+        self.platform_solids.draw_instanced(self.platform_transforms.len());
 
         unsafe {
-            gl::UniformMatrix4fv(self.unif_view, 1, gl::FALSE, view.as_ptr());
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
         }
     }
 
-    pub fn draw_platform(&self, surface_center: Vector3<f32>, surface_dim: (f32, f32), height: f32, color: (f32, f32, f32)) {
-        let scale = Matrix4::from_nonuniform_scale(surface_dim.0, surface_dim.1, height);
-        let translate = Matrix4::from_translation(surface_center - Vector3::new(0.0, 0.0, height/2.0));
+    fn light_matrix(&self) -> Matrix4<f32> {
+        self.lights.light_matrix(self.platform_bounds.0, self.platform_bounds.1)
+    }
+
+    /// Fills the viewport with the sky and background layers, scrolled to
+    /// how far the camera has turned. Leaves the depth buffer alone.
+    pub fn draw_background(&self) {
+        // the camera's angle around the level, back from where it looks
+        let angle = (-self.eye_direction.x).atan2(-self.eye_direction.y);
+        self.background.draw(angle / std::f32::consts::TAU);
+    }
+
+    /// Draws every platform passed to the last `set_platforms` call, with
+    /// one draw call per atlas page for the faces and one for the edges.
+    pub fn draw_platforms(&self) {
+        self.draw_platform_instances(false);
+    }
 
-        let transform = translate*scale;
+    /// Like `draw_platforms`, but with two draw calls per platform the way
+    /// they were drawn before instancing, to compare the two in `--bench`.
+    pub fn draw_platforms_one_by_one(&self) {
+        self.draw_platform_instances(true);
+    }
 
+    fn draw_platform_instances(&self, one_by_one: bool) {
         unsafe {
-            gl::UniformMatrix4fv(self.unif_model, 1, gl::FALSE, transform.as_ptr());
-            gl::Uniform3f(self.unif_color, color.0, color.1, color.2);
-            gl::Uniform1i(self.unif_apply_diffuse, 1);
+            gl::ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D, self.shadow_target.depth_texture().name());
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        let program = self.platform_program.bind();
+        self.unif_platform_lighting.set(&program, &self.lights, self.eye_direction, self.light_matrix());
+        program.set(&self.unif_platform_use_instance_color, true);
+        program.set(&self.unif_platform_apply_diffuse, true);
+
+        for (page, range) in &self.platform_batches {
+            program.set(&self.unif_platform_textured, page.is_some());
+            if let Some(page) = page {
+                unsafe {
+                    gl::BindTexture(gl::TEXTURE_2D, self.atlas_pages[*page].0.name());
+                }
+            }
 
-            gl::BindVertexArray(self.platform_faces_vao.name());
-            gl::DrawArrays(gl::TRIANGLES, 0, vertex_data::PLATFORM_FACES.len() as GLint);
+            self.draw_platform_range(&self.platform_faces, range.clone(), one_by_one);
+        }
+
+        // edges have no normals to light
+        program.set(&self.unif_platform_textured, false);
+        program.set(&self.unif_platform_use_instance_color, false);
+        program.set(&self.unif_platform_apply_diffuse, false);
+        program.set(&self.unif_platform_color, Vector3::new(0.0, 0.0, 0.0));
 
-            
-            gl::Uniform3f(self.unif_color, 0.0, 0.0, 0.0);
-            gl::Uniform1i(self.unif_apply_diffuse, 1);
-            
-            gl::BindVertexArray(self.platform_edges_vao.name());
-            gl::DrawArrays(gl::LINES, 0, vertex_data::PLATFORM_EDGES.len() as GLint);
+        self.draw_platform_range(&self.platform_edges, 0..self.platform_transforms.len(), one_by_one);
+    }
+
+    // draws start from the first instance given, by pointing the instance
+    // attributes of `mesh` at it
+    fn draw_platform_range<V: VertexLayout>(&self, mesh: &Mesh<V>, instances: Range<usize>, one_by_one: bool) {
+        mesh.bind();
+        if one_by_one {
+            for i in instances {
+                self.platform_instance_attribs.bind(&self.platform_transforms, &self.platform_colors, &self.platform_uv_rects, &self.platform_shininess, i);
+                mesh.draw_instanced(1);
+            }
+        } else {
+            self.platform_instance_attribs.bind(&self.platform_transforms, &self.platform_colors, &self.platform_uv_rects, &self.platform_shininess, instances.start);
+            mesh.draw_instanced(instances.len());
         }
     }
 
     pub fn draw_square(&self, base: Vector3<f32>, side: f32, color: (f32, f32, f32)) {
+        self.draw_billboard(base, side, color, None);
+    }
+
+    /// Draws a camera-facing square showing the atlas texture named
+    /// `texture`. Texels with an alpha below one half are discarded.
+    pub fn draw_sprite(&self, base: Vector3<f32>, side: f32, texture: &str) {
+        let texture = self.atlas_entries.get(texture)
+            .map(|entry| (entry.page, entry.uv_rect(self.atlas_pages[entry.page].1)));
+        self.draw_billboard(base, side, (1.0, 1.0, 1.0), texture);
+    }
+
+    /// Like `draw_sprite`, but only shows one cell of the texture.
+    pub fn draw_sprite_frame(&self, base: Vector3<f32>, side: f32, frame: &SpriteFrame) {
+        self.draw_billboard(base, side, (1.0, 1.0, 1.0), self.frame_texture(frame));
+    }
+
+    /// Draws the parts of a sprite frame that are hidden behind what was
+    /// drawn before, as a flat see-through silhouette. Does nothing when
+    /// turned off in the settings.
+    pub fn draw_silhouette(&self, base: Vector3<f32>, side: f32, frame: &SpriteFrame) {
+        let (page, uv_rect) = match self.frame_texture(frame) {
+            Some(texture) if self.silhouette.enabled => texture,
+            _ => return,
+        };
+
+        let program = self.silhouette_program.bind();
+        program.set(&self.unif_silhouette_model, self.billboard_transform(base, side));
+        program.set(&self.unif_silhouette_uv_rect, uv_rect.into());
+        program.set(&self.unif_silhouette_tint, self.silhouette.tint);
+        program.set(&self.unif_silhouette_opacity, self.silhouette.opacity);
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.atlas_pages[page].0.name());
+            // only where something is in front, without covering it up
+            gl::DepthFunc(gl::GREATER);
+            gl::DepthMask(gl::FALSE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        self.square_faces.draw();
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::DepthMask(gl::TRUE);
+            gl::DepthFunc(gl::LESS);
+        }
+    }
+
+    /// Draws `particles` facing the camera, blended over everything drawn
+    /// so far.
+    pub fn draw_particles(&self, particles: &[Particle]) {
+        self.particles.draw(particles);
+    }
+
+    // the page and uv rect of one cell of a sprite sheet
+    fn frame_texture(&self, frame: &SpriteFrame) -> Option<(usize, [f32; 4])> {
+        self.atlas_entries.get(frame.texture)
+            .map(|entry| {
+                let [u, v, w, h] = entry.uv_rect(self.atlas_pages[entry.page].1);
+                let (w, h) = (w / frame.grid.0 as f32, h / frame.grid.1 as f32);
+                let (u, v) = (u + frame.cell.0 as f32 * w, v + frame.cell.1 as f32 * h);
+
+                // a negative width samples the cell right to left
+                let uv_rect = if frame.flip_x {
+                    [u + w, v, -w, h]
+                } else {
+                    [u, v, w, h]
+                };
+                (entry.page, uv_rect)
+            })
+    }
+
+    /// Draws a soft shadow on the face of `platform` towards the camera,
+    /// hanging from `base` on its top. `opacity` is the darkness in the
+    /// middle.
+    pub fn draw_drop_shadow(&self, platform: &Platform, base: Vector3<f32>, width: f32, opacity: f32) {
+        // just in front of the face, whichever axis the camera looks along
+        let mut base = base;
+        let center = Vector3::from(platform.surface_center);
+        if self.eye_direction.x.abs() > self.eye_direction.y.abs() {
+            base.x = center.x + self.eye_direction.x.signum()*(platform.surface_dim.0/2.0 + 0.01);
+        } else {
+            base.y = center.y + self.eye_direction.y.signum()*(platform.surface_dim.1/2.0 + 0.01);
+        }
+
+        // the top half of the square is cut off in the shader
+        let scale = Matrix4::from_nonuniform_scale(width, width/2.0, 1.0);
+        let rotate = Matrix4::from_angle_x(cgmath::Deg(90.0));
+        let translate = Matrix4::from_translation(base);
+
+        let program = self.blob_program.bind();
+        program.set(&self.unif_blob_model, translate*self.camera_inverse_rotation*rotate*scale);
+        program.set(&self.unif_blob_opacity, opacity);
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        self.square_faces.draw();
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+    }
+
+    // a camera-facing square standing on `base`
+    fn billboard_transform(&self, base: Vector3<f32>, side: f32) -> Matrix4<f32> {
         let scale = Matrix4::from_scale(side);
         let rotate = Matrix4::from_angle_x(cgmath::Deg(90.0));
         let rotate2 = &self.camera_inverse_rotation;
         let translate = Matrix4::from_translation(base + Vector3::new(0.0, 0.0, side/2.0));
 
-        let transform = translate*rotate2*rotate*scale;
+        translate*rotate2*rotate*scale
+    }
 
-        unsafe {
-            gl::UniformMatrix4fv(self.unif_model, 1, gl::FALSE, transform.as_ptr());
-            gl::Uniform3f(self.unif_color, color.0, color.1, color.2);
-            gl::Uniform1i(self.unif_apply_diffuse, 0);
+    fn draw_billboard(&self, base: Vector3<f32>, side: f32, color: (f32, f32, f32), texture: Option<(usize, [f32; 4])>) {
+        let transform = self.billboard_transform(base, side);
+
+        let program = self.shader_program.bind();
+        self.unif_lighting.set(&program, &self.lights, self.eye_direction, self.light_matrix());
+        program.set(&self.unif_model, transform);
+        program.set(&self.unif_color, color.into());
+        program.set(&self.unif_apply_diffuse, false);
+        program.set(&self.unif_shininess, 0.0);
+
+        program.set(&self.unif_textured, texture.is_some());
+        if let Some((page, uv_rect)) = texture {
+            program.set(&self.unif_uv_rect, uv_rect.into());
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, self.atlas_pages[page].0.name());
+            }
+        }
+
+        self.square_faces.draw();
+    }
+}
 
-            gl::BindVertexArray(self.square_faces_vao.name());
-            gl::DrawArrays(gl::TRIANGLES, 0, vertex_data::SQUARE_FACES.len() as GLint);
+/// How `draw_silhouette` looks, from the settings.
+struct Silhouette {
+    enabled: bool,
+    tint: Vector3<f32>,
+    opacity: f32,
+}
+
+impl Default for Silhouette {
+    fn default() -> Silhouette {
+        Silhouette {
+            enabled: true,
+            tint: Vector3::new(0.85, 0.9, 1.0),
+            opacity: 0.5,
         }
     }
 }
+
+/// Attribute locations of the per-instance platform data, `None` for any
+/// the shader doesn't read.
+struct InstanceAttribs {
+    model: Option<GLuint>,
+    color: Option<GLuint>,
+    uv_rect: Option<GLuint>,
+    shininess: Option<GLuint>,
+}
+
+impl InstanceAttribs {
+    /// Points the attributes of the currently bound vao at the instance
+    /// buffers, starting from instance `first`.
+    fn bind(&self, transforms: &Buffer<Matrix4<f32>>, colors: &Buffer<[GLfloat; 3]>, uv_rects: &Buffer<[GLfloat; 4]>, shininess: &Buffer<GLfloat>, first: usize) {
+        use std::mem::size_of;
+
+        if let Some(model) = self.model {
+            let stride = size_of::<Matrix4<f32>>();
+            transforms.bind();
+            // a mat4 attribute takes up four consecutive locations, one per column
+            for i in 0..4 {
+                bind_instance_attrib(model + i, 4, stride, first*stride + 4*i as usize*size_of::<GLfloat>());
+            }
+        }
+        if let Some(color) = self.color {
+            colors.bind();
+            bind_instance_attrib(color, 3, size_of::<[GLfloat; 3]>(), first*size_of::<[GLfloat; 3]>());
+        }
+        if let Some(uv_rect) = self.uv_rect {
+            uv_rects.bind();
+            bind_instance_attrib(uv_rect, 4, size_of::<[GLfloat; 4]>(), first*size_of::<[GLfloat; 4]>());
+        }
+        if let Some(shininess_attrib) = self.shininess {
+            shininess.bind();
+            bind_instance_attrib(shininess_attrib, 1, size_of::<GLfloat>(), first*size_of::<GLfloat>());
+        }
+    }
+}
+
+// a float attribute at `location`, advancing once per instance through the
+// bound array buffer
+fn bind_instance_attrib(location: GLuint, components: GLint, stride: usize, offset: usize) {
+    unsafe {
+        gl::VertexAttribPointer(location, components, gl::FLOAT, gl::FALSE, stride as GLint, offset as *const _);
+        gl::EnableVertexAttribArray(location);
+        gl::VertexAttribDivisor(location, 1);
+    }
+}
@@ -0,0 +1,429 @@
+//! Packs named pngs into one or more atlas pages.
+//!
+//! Images are placed on shelves, sorted by height, and surrounded by
+//! `PADDING` pixels copied from their own edges so linear filtering never
+//! samples a neighbour. The result can be saved to a single file, laid out
+//! as follows (all integers little-endian u32):
+//!
+//! ```text
+//! "FEZATLS1"
+//! page count, then for each page: png length, png bytes
+//! entry count, then for each entry: name length, name (utf-8), page, x, y, w, h
+//! ```
+//!
+//! The cache written by `Atlas::write_cache` and read by `Atlas::load_cached`
+//! puts a little-endian u64 hash of the source pngs in front of that, to
+//! tell when it is stale.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use super::image::{Image, invalid_data};
+use crate::gfx::Error;
+
+const MAGIC: &[u8; 8] = b"FEZATLS1";
+
+/// Maximum side of an atlas page, in pixels.
+pub const MAX_PAGE_SIZE: u32 = 1024;
+/// Pixels of extruded border around each image.
+pub const PADDING: u32 = 2;
+
+/// Where an image ended up, in pixels, excluding the padding.
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasEntry {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl AtlasEntry {
+    /// Offset and size of the entry in texture coordinates of a
+    /// `page_size` page, as `[u, v, width, height]`.
+    pub fn uv_rect(&self, page_size: (u32, u32)) -> [f32; 4] {
+        let (pw, ph) = (page_size.0 as f32, page_size.1 as f32);
+        [self.x as f32 / pw, self.y as f32 / ph, self.w as f32 / pw, self.h as f32 / ph]
+    }
+}
+
+pub struct Atlas {
+    pub pages: Vec<Image>,
+    pub entries: HashMap<String, AtlasEntry>,
+}
+
+impl Atlas {
+    /// Packs the given images into as few pages as possible.
+    pub fn build(mut images: Vec<(String, Image)>) -> io::Result<Atlas> {
+        // tallest first makes for tighter shelves; names break ties so the
+        // output doesn't depend on directory order
+        images.sort_by(|(a_name, a), (b_name, b)| b.height.cmp(&a.height).then(a_name.cmp(b_name)));
+
+        // first pass: find positions
+        let mut placements = Vec::with_capacity(images.len());
+        let mut page_extents: Vec<(u32, u32)> = Vec::new();
+        let (mut page, mut x, mut y, mut shelf_height) = (0, 0, 0, 0);
+        for (name, image) in &images {
+            let w = image.width + 2*PADDING;
+            let h = image.height + 2*PADDING;
+            if w > MAX_PAGE_SIZE || h > MAX_PAGE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("{} is too large for a {}x{} atlas page", name, MAX_PAGE_SIZE, MAX_PAGE_SIZE)));
+            }
+
+            if x + w > MAX_PAGE_SIZE {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            if y + h > MAX_PAGE_SIZE {
+                page += 1;
+                x = 0;
+                y = 0;
+                shelf_height = 0;
+            }
+            if page_extents.len() <= page {
+                page_extents.push((0, 0));
+            }
+
+            placements.push(AtlasEntry {
+                page,
+                x: x + PADDING,
+                y: y + PADDING,
+                w: image.width,
+                h: image.height,
+            });
+
+            x += w;
+            shelf_height = shelf_height.max(h);
+            let extent = &mut page_extents[page];
+            *extent = (extent.0.max(x), extent.1.max(y + h));
+        }
+
+        // second pass: blit into pages trimmed to a power of two
+        let mut pages: Vec<Image> = page_extents.iter()
+            .map(|&(w, h)| {
+                let (width, height) = (w.next_power_of_two(), h.next_power_of_two());
+                Image {
+                    width,
+                    height,
+                    pixels: vec![0; 4*(width*height) as usize],
+                }
+            })
+            .collect();
+
+        let mut entries = HashMap::new();
+        for ((name, image), entry) in images.into_iter().zip(placements) {
+            let page = &mut pages[entry.page];
+
+            let pad = PADDING as i64;
+            for dy in -pad..image.height as i64 + pad {
+                for dx in -pad..image.width as i64 + pad {
+                    // clamping the source extrudes the edges into the padding
+                    let sx = dx.max(0).min(image.width as i64 - 1) as u32;
+                    let sy = dy.max(0).min(image.height as i64 - 1) as u32;
+                    let tx = (entry.x as i64 + dx) as u32;
+                    let ty = (entry.y as i64 + dy) as u32;
+
+                    let i = 4*(ty*page.width + tx) as usize;
+                    page.pixels[i..i + 4].copy_from_slice(image.pixel(sx, sy));
+                }
+            }
+
+            entries.insert(name, entry);
+        }
+
+        Ok(Atlas {
+            pages,
+            entries,
+        })
+    }
+
+    /// Packs `(name, png)` pairs.
+    pub fn from_pngs(sources: &[(&str, &[u8])]) -> Result<Atlas, Error> {
+        let images = sources.iter()
+            .map(|&(name, png)| {
+                let image = Image::decode_png(png)
+                    .map_err(|error| Error::ImageDecode { path: Some(name.into()), error })?;
+                Ok((name.to_string(), image))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Self::build(images).map_err(|error| Error::Io { path: None, error })
+    }
+
+    /// Loads the atlas cached at `cache`, packing `sources` like
+    /// `from_pngs` instead if the cache is missing, unreadable or was
+    /// packed from different pngs. A cache that can't be written is only
+    /// warned about.
+    pub fn load_cached<P: AsRef<Path>>(sources: &[(&str, &[u8])], cache: P) -> Result<Atlas, Error> {
+        let cache = cache.as_ref();
+        match Self::read_cache(cache, source_hash(sources)) {
+            Ok(Some(atlas)) => return Ok(atlas),
+            Ok(None) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => log::warn!("{}, repacking it", Error::Io { path: Some(cache.into()), error }),
+        }
+
+        let atlas = Self::from_pngs(sources)?;
+        if let Err(error) = atlas.write_cache(sources, cache) {
+            log::warn!("could not cache the atlas: {}", Error::Io { path: Some(cache.into()), error });
+        }
+
+        Ok(atlas)
+    }
+
+    // `None` if the cache was packed from other sources
+    fn read_cache(cache: &Path, hash: u64) -> io::Result<Option<Atlas>> {
+        let mut input = io::BufReader::new(fs::File::open(cache)?);
+        let mut cached_hash = [0; 8];
+        input.read_exact(&mut cached_hash)?;
+        if u64::from_le_bytes(cached_hash) != hash {
+            return Ok(None);
+        }
+
+        Self::read(input).map(Some)
+    }
+
+    /// Saves the atlas to `cache` for `load_cached`, as packed from
+    /// `sources`.
+    pub fn write_cache<P: AsRef<Path>>(&self, sources: &[(&str, &[u8])], cache: P) -> io::Result<()> {
+        let cache = cache.as_ref();
+        if let Some(parent) = cache.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut output = io::BufWriter::new(fs::File::create(cache)?);
+        output.write_all(&source_hash(sources).to_le_bytes())?;
+        self.write(output)
+    }
+
+    pub fn write<W: Write>(&self, mut output: W) -> io::Result<()> {
+        output.write_all(MAGIC)?;
+
+        write_u32(&mut output, self.pages.len() as u32)?;
+        for page in &self.pages {
+            let mut png = Vec::new();
+            page.encode_png(&mut png)?;
+
+            write_u32(&mut output, png.len() as u32)?;
+            output.write_all(&png)?;
+        }
+
+        // sorted, so that rebuilding the same images gives the same file
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(name, _)| *name);
+
+        write_u32(&mut output, entries.len() as u32)?;
+        for (name, entry) in entries {
+            write_u32(&mut output, name.len() as u32)?;
+            output.write_all(name.as_bytes())?;
+            for value in &[entry.page as u32, entry.x, entry.y, entry.w, entry.h] {
+                write_u32(&mut output, *value)?;
+            }
+        }
+
+        output.flush()
+    }
+
+    pub fn read<R: Read>(mut input: R) -> io::Result<Atlas> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an atlas file"));
+        }
+
+        let page_count = read_u32(&mut input)?;
+        let mut pages = Vec::with_capacity(page_count as usize);
+        for _ in 0..page_count {
+            let len = read_u32(&mut input)?;
+            let mut png = vec![0; len as usize];
+            input.read_exact(&mut png)?;
+
+            pages.push(Image::decode_png(&png[..])?);
+        }
+
+        let entry_count = read_u32(&mut input)?;
+        let mut entries = HashMap::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let len = read_u32(&mut input)?;
+            let mut name = vec![0; len as usize];
+            input.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(invalid_data)?;
+
+            let page = read_u32(&mut input)? as usize;
+            if page >= pages.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "atlas entry refers to a missing page"));
+            }
+
+            entries.insert(name, AtlasEntry {
+                page,
+                x: read_u32(&mut input)?,
+                y: read_u32(&mut input)?,
+                w: read_u32(&mut input)?,
+                h: read_u32(&mut input)?,
+            });
+        }
+
+        Ok(Atlas {
+            pages,
+            entries,
+        })
+    }
+}
+
+/// Reads every png in `dir`, named after its file stem, in the order
+/// `build.rs` lists them for `resources::TEXTURES`.
+pub fn read_pngs<P: AsRef<Path>>(dir: P) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.into_iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            Ok((name, fs::read(path)?))
+        })
+        .collect()
+}
+
+// FNV-1a over the names and pngs, stable between runs and builds
+fn source_hash(sources: &[(&str, &[u8])]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &(name, png) in sources {
+        // with lengths, so moving bytes between name and png changes it
+        let (name_len, png_len) = ((name.len() as u64).to_le_bytes(), (png.len() as u64).to_le_bytes());
+        for bytes in [&name_len[..], name.as_bytes(), &png_len[..], png] {
+            for &byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    hash
+}
+
+fn write_u32<W: Write>(output: &mut W, value: u32) -> io::Result<()> {
+    output.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: color.repeat((width*height) as usize),
+        }
+    }
+
+    #[test]
+    fn packs_onto_shelves() {
+        let atlas = Atlas::build(vec![
+            ("short".to_string(), solid(8, 4, [255, 0, 0, 255])),
+            ("tall".to_string(), solid(4, 8, [0, 255, 0, 255])),
+        ]).unwrap();
+
+        // tallest first, side by side on one shelf
+        assert_eq!(atlas.pages.len(), 1);
+        let (tall, short) = (atlas.entries["tall"], atlas.entries["short"]);
+        assert_eq!((tall.page, tall.x, tall.y, tall.w, tall.h), (0, PADDING, PADDING, 4, 8));
+        assert_eq!((short.page, short.x, short.y, short.w, short.h), (0, 4 + 3*PADDING, PADDING, 8, 4));
+
+        // trimmed to a power of two, with the edges extruded into the padding
+        let page = &atlas.pages[0];
+        assert_eq!((page.width, page.height), (32, 16));
+        assert_eq!(page.pixel(0, 0), [0, 255, 0, 255]);
+        assert_eq!(page.pixel(short.x + 8 + PADDING - 1, short.y + 4 + PADDING - 1), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn overflows_onto_new_pages() {
+        // only one fits on a page, across or down
+        let side = MAX_PAGE_SIZE/2 + 1;
+        let atlas = Atlas::build(vec![
+            ("a".to_string(), solid(side, side, [255; 4])),
+            ("b".to_string(), solid(side, side, [255; 4])),
+        ]).unwrap();
+
+        assert_eq!(atlas.pages.len(), 2);
+        assert_eq!((atlas.entries["a"].page, atlas.entries["b"].page), (0, 1));
+        assert_eq!((atlas.entries["b"].x, atlas.entries["b"].y), (PADDING, PADDING));
+    }
+
+    #[test]
+    fn rejects_oversize_images() {
+        let side = MAX_PAGE_SIZE - 2*PADDING + 1;
+        assert!(Atlas::build(vec![("huge".to_string(), solid(side, 1, [255; 4]))]).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let atlas = Atlas::build(vec![
+            ("a".to_string(), solid(3, 5, [10, 20, 30, 40])),
+            ("b".to_string(), solid(6, 2, [50, 60, 70, 80])),
+        ]).unwrap();
+
+        let mut file = Vec::new();
+        atlas.write(&mut file).unwrap();
+        assert!(file.starts_with(MAGIC));
+        let read = Atlas::read(&file[..]).unwrap();
+
+        assert_eq!(read.pages.len(), atlas.pages.len());
+        for (read, page) in read.pages.iter().zip(&atlas.pages) {
+            assert_eq!((read.width, read.height), (page.width, page.height));
+            assert_eq!(read.pixels, page.pixels);
+        }
+        assert_eq!(read.entries.len(), atlas.entries.len());
+        for (name, entry) in &atlas.entries {
+            let read = read.entries[name];
+            assert_eq!((read.page, read.x, read.y, read.w, read.h), (entry.page, entry.x, entry.y, entry.w, entry.h));
+        }
+
+        assert!(Atlas::read(&b"FEZATLS0"[..]).is_err());
+        assert!(Atlas::read(&file[..file.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn caches_by_source() {
+        let mut png = Vec::new();
+        solid(2, 2, [255; 4]).encode_png(&mut png).unwrap();
+        let sources = [("white", &png[..])];
+        let cache = std::env::temp_dir().join(format!("fez-test-{}.atlas", std::process::id()));
+
+        // an atlas that packing `sources` wouldn't give, to tell the two apart
+        let cached = Atlas::build(Vec::new()).unwrap();
+        cached.write_cache(&sources, &cache).unwrap();
+        assert!(Atlas::load_cached(&sources, &cache).unwrap().entries.is_empty());
+
+        // other sources repack, and replace the cache
+        let other = [("other", &png[..])];
+        assert!(Atlas::load_cached(&other, &cache).unwrap().entries.contains_key("other"));
+        assert!(Atlas::load_cached(&other, &cache).unwrap().entries.contains_key("other"));
+
+        fs::remove_file(&cache).unwrap();
+    }
+
+    #[test]
+    fn embeds_the_texture_dir() {
+        // so `--build-atlas` writes the cache the game looks for
+        let pngs = read_pngs(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tex")).unwrap();
+        let sources = pngs.iter().map(|(name, png)| (&name[..], &png[..])).collect::<Vec<_>>();
+        assert_eq!(source_hash(&sources), source_hash(crate::resources::TEXTURES));
+    }
+}
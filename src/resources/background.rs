@@ -0,0 +1,113 @@
+//! The level's sky and background layers, drawn by `background.frag` in one
+//! fullscreen pass.
+
+use cgmath::prelude::*;
+use cgmath::{Vector3, Vector4};
+
+use super::shader_sources::ShaderSources;
+use crate::c_str;
+use crate::gfx::*;
+use crate::level::{Background, LayerShape};
+
+/// How many layers the shader draws, as in `background.frag`.
+pub const MAX_LAYERS: usize = 4;
+
+const STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "fullscreen.vert"), (ShaderType::Fragment, "background.frag")];
+
+pub struct BackgroundRenderer {
+    program: ShaderProgram,
+    unif_sky_top: Uniform<Vector3<f32>>,
+    unif_sky_bottom: Uniform<Vector3<f32>>,
+    unif_turns: Uniform<f32>,
+    unif_layer_count: Uniform<i32>,
+    unif_layer_colors: Uniform<[Vector4<f32>; MAX_LAYERS]>,
+    unif_layer_shapes: Uniform<[Vector4<f32>; MAX_LAYERS]>,
+    unif_layer_seeds: Uniform<[f32; MAX_LAYERS]>,
+
+    // core profile wants a vao bound even without attributes
+    vao: VertexArrayObject,
+
+    sky_top: Vector3<f32>,
+    sky_bottom: Vector3<f32>,
+    layer_count: i32,
+    // rgb, and whether it's a skyline
+    layer_colors: [Vector4<f32>; MAX_LAYERS],
+    // height, amplitude, bumps and parallax
+    layer_shapes: [Vector4<f32>; MAX_LAYERS],
+    layer_seeds: [f32; MAX_LAYERS],
+}
+
+impl BackgroundRenderer {
+    pub fn new(shaders: &ShaderSources) -> Result<BackgroundRenderer, Error> {
+        let program = shaders.build_program("background", STAGES, &[])?;
+
+        let vao = VertexArrayObject::new();
+        vao.set_label("background");
+
+        let mut renderer = BackgroundRenderer {
+            unif_sky_top: program.uniform(c_str!("sky_top"))?,
+            unif_sky_bottom: program.uniform(c_str!("sky_bottom"))?,
+            unif_turns: program.uniform(c_str!("turns"))?,
+            unif_layer_count: program.uniform(c_str!("layer_count"))?,
+            unif_layer_colors: program.uniform(c_str!("layer_colors"))?,
+            unif_layer_shapes: program.uniform(c_str!("layer_shapes"))?,
+            unif_layer_seeds: program.uniform(c_str!("layer_seeds"))?,
+            program,
+
+            vao,
+
+            sky_top: Vector3::zero(),
+            sky_bottom: Vector3::zero(),
+            layer_count: 0,
+            layer_colors: [Vector4::zero(); MAX_LAYERS],
+            layer_shapes: [Vector4::zero(); MAX_LAYERS],
+            layer_seeds: [0.0; MAX_LAYERS],
+        };
+        renderer.set_background(&Background::plain((0.0, 0.0, 0.0)));
+
+        Ok(renderer)
+    }
+
+    pub fn reload_shaders(&self, shaders: &ShaderSources, changed: &[String]) {
+        shaders.reload(&self.program, "background", STAGES, &[], changed);
+    }
+
+    /// Only the first `MAX_LAYERS` layers are kept.
+    pub fn set_background(&mut self, background: &Background) {
+        super::warn_over_limit("background layers", background.layers.len(), MAX_LAYERS);
+
+        self.sky_top = background.sky_top.into();
+        self.sky_bottom = background.sky_bottom.into();
+        self.layer_count = background.layers.len().min(MAX_LAYERS) as i32;
+        for (i, layer) in background.layers.iter().take(MAX_LAYERS).enumerate() {
+            let skyline = match layer.shape {
+                LayerShape::Hills => 0.0,
+                LayerShape::Skyline => 1.0,
+            };
+            self.layer_colors[i] = Vector3::from(layer.color).extend(skyline);
+            self.layer_shapes[i] = Vector4::new(layer.height, layer.amplitude, layer.bumps as f32, layer.parallax);
+            self.layer_seeds[i] = layer.seed;
+        }
+    }
+
+    /// Covers the whole viewport, without touching the depth buffer.
+    /// `turns` is how far the camera has turned around the level, which
+    /// the layers scroll with.
+    pub fn draw(&self, turns: f32) {
+        let program = self.program.bind();
+        program.set(&self.unif_sky_top, self.sky_top);
+        program.set(&self.unif_sky_bottom, self.sky_bottom);
+        program.set(&self.unif_turns, turns);
+        program.set(&self.unif_layer_count, self.layer_count);
+        program.set(&self.unif_layer_colors, self.layer_colors);
+        program.set(&self.unif_layer_shapes, self.layer_shapes);
+        program.set(&self.unif_layer_seeds, self.layer_seeds);
+
+        self.vao.bind();
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Bitmap fonts in the text format of AngelCode's BMFont: one line per
+//! block, each a tag followed by `key=value` pairs.
+//!
+//! ```text
+//! common lineHeight=10 base=7 scaleW=96 scaleH=70 pages=1
+//! page id=0 file="pixel.png"
+//! char id=65 x=0 y=10 width=5 height=9 xoffset=0 yoffset=0 xadvance=6 page=0
+//! kerning first=70 second=65 amount=-1
+//! ```
+//!
+//! Only single page fonts are supported. Blocks and keys not needed for
+//! layout, like `info`, are ignored.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+/// Drawn in place of characters the font doesn't have.
+const REPLACEMENT: char = '?';
+
+/// Where a character is in the page, and how it sits on the line, in
+/// pixels.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Glyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub xoffset: f32,
+    pub yoffset: f32,
+    pub xadvance: f32,
+}
+
+pub struct Font {
+    /// Distance between the tops of two lines.
+    pub line_height: f32,
+    /// Distance from the top of a line to the baseline.
+    pub base: f32,
+    /// Size of the page, which every glyph lies within.
+    pub page_size: (u32, u32),
+    /// The page's image file, relative to the font file.
+    pub page: PathBuf,
+
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f32>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// How `Font::layout` arranges text.
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    /// Lines start at the origin, are centered on it or end at it.
+    pub align: Align,
+    /// Lines longer than this are wrapped between words, or within a word
+    /// that doesn't fit on a line of its own.
+    pub max_width: Option<f32>,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            align: Align::Left,
+            max_width: None,
+        }
+    }
+}
+
+/// A glyph placed by `Font::layout`, its top left corner relative to the
+/// origin with y pointing down.
+#[derive(Clone, Copy, Debug)]
+pub struct PlacedGlyph {
+    pub position: (f32, f32),
+    pub glyph: Glyph,
+}
+
+impl Font {
+    /// Reads the contents of a `.fnt` file. Positions and sizes in the
+    /// page can't be negative, and glyphs must fit in the page.
+    pub fn parse(text: &str) -> Result<Font, String> {
+        let mut font = Font {
+            line_height: 0.0,
+            base: 0.0,
+            page_size: (0, 0),
+            page: PathBuf::new(),
+
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+        };
+        let mut has_page = false;
+
+        for (i, line) in text.lines().enumerate() {
+            let (tag, values) = parse_line(line)
+                .map_err(|err| format!("line {}: {}", i + 1, err))?;
+            let get = |key: &str| -> Result<i64, String> {
+                values.get(key)
+                    .ok_or_else(|| format!("line {}: {} has no {}", i + 1, tag, key))?
+                    .parse()
+                    .map_err(|_| format!("line {}: {} is not a number", i + 1, key))
+            };
+            let get_unsigned = |key: &str| -> Result<u32, String> {
+                let value = get(key)?;
+                u32::try_from(value)
+                    .map_err(|_| format!("line {}: {} is out of range: {}", i + 1, key, value))
+            };
+
+            match tag {
+                "common" => {
+                    if get("pages")? != 1 {
+                        return Err(format!("line {}: only fonts with one page are supported", i + 1));
+                    }
+                    font.line_height = get("lineHeight")? as f32;
+                    font.base = get("base")? as f32;
+                    font.page_size = (get_unsigned("scaleW")?, get_unsigned("scaleH")?);
+                },
+                "page" => {
+                    font.page = values.get("file")
+                        .ok_or_else(|| format!("line {}: page has no file", i + 1))?
+                        .into();
+                    has_page = true;
+                },
+                "char" => {
+                    let id = get("id")?;
+                    let c = u32::try_from(id).ok().and_then(char::from_u32)
+                        .ok_or_else(|| format!("line {}: {} is not a character", i + 1, id))?;
+                    font.glyphs.insert(c, Glyph {
+                        x: get_unsigned("x")?,
+                        y: get_unsigned("y")?,
+                        width: get_unsigned("width")?,
+                        height: get_unsigned("height")?,
+                        xoffset: get("xoffset")? as f32,
+                        yoffset: get("yoffset")? as f32,
+                        xadvance: get("xadvance")? as f32,
+                    });
+                },
+                "kerning" => {
+                    let pair = (char::from_u32(get_unsigned("first")?), char::from_u32(get_unsigned("second")?));
+                    if let (Some(first), Some(second)) = pair {
+                        font.kerning.insert((first, second), get("amount")? as f32);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        if !has_page {
+            return Err("no page".to_string());
+        }
+        for (c, glyph) in &font.glyphs {
+            let right = glyph.x.checked_add(glyph.width);
+            let bottom = glyph.y.checked_add(glyph.height);
+            if right.is_none_or(|right| right > font.page_size.0) || bottom.is_none_or(|bottom| bottom > font.page_size.1) {
+                return Err(format!("glyph {:?} is outside the page", c));
+            }
+        }
+        Ok(font)
+    }
+
+    /// The glyph for `c`, or the replacement glyph if there is none. `None`
+    /// if the font has neither.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&REPLACEMENT))
+    }
+
+    fn advance(&self, previous: Option<char>, c: char) -> f32 {
+        let kerning = previous
+            .and_then(|previous| self.kerning.get(&(previous, c)))
+            .copied()
+            .unwrap_or(0.0);
+        kerning + self.glyph(c).map_or(0.0, |glyph| glyph.xadvance)
+    }
+
+    /// Width of `text` on a single line.
+    pub fn width(&self, text: &str) -> f32 {
+        let mut previous = None;
+        text.chars()
+            .map(|c| {
+                let advance = self.advance(previous, c);
+                previous = Some(c);
+                advance
+            })
+            .sum()
+    }
+
+    /// Splits `text` into lines: at newlines, and between words to keep
+    /// lines within `max_width`.
+    pub fn wrap<'a>(&self, text: &'a str, max_width: Option<f32>) -> Vec<&'a str> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+            let max_width = match max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(paragraph);
+                    continue;
+                },
+            };
+
+            let mut start = 0;
+            let mut end = 0;
+            for (word_start, word) in words(paragraph) {
+                let word_end = word_start + word.len();
+                if end > start && self.width(&paragraph[start..word_end]) <= max_width {
+                    end = word_end;
+                    continue;
+                }
+
+                // the word goes on a new line, or starts the first one
+                if end > start {
+                    lines.push(&paragraph[start..end]);
+                }
+                start = word_start;
+                end = word_start;
+                // cut up words that don't fit on any line, keeping at
+                // least a character per line
+                for (i, c) in word.char_indices() {
+                    let next = word_start + i + c.len_utf8();
+                    if end > start && self.width(&paragraph[start..next]) > max_width {
+                        lines.push(&paragraph[start..end]);
+                        start = end;
+                    }
+                    end = next;
+                }
+            }
+            lines.push(&paragraph[start..end]);
+        }
+
+        lines
+    }
+
+    /// Positions the glyphs of `text`, a line every `line_height`.
+    /// Characters without a glyph get the replacement glyph, if the font
+    /// has one.
+    pub fn layout(&self, text: &str, layout: &Layout) -> Vec<PlacedGlyph> {
+        let mut placed = Vec::new();
+        for (row, line) in self.wrap(text, layout.max_width).into_iter().enumerate() {
+            let mut x = match layout.align {
+                Align::Left => 0.0,
+                Align::Center => -0.5*self.width(line),
+                Align::Right => -self.width(line),
+            };
+            let y = row as f32 * self.line_height;
+
+            let mut previous = None;
+            for c in line.chars() {
+                let glyph = match self.glyph(c) {
+                    Some(glyph) => *glyph,
+                    None => continue,
+                };
+                x += previous
+                    .and_then(|previous| self.kerning.get(&(previous, c)))
+                    .copied()
+                    .unwrap_or(0.0);
+
+                if glyph.width > 0 && glyph.height > 0 {
+                    placed.push(PlacedGlyph {
+                        position: (x + glyph.xoffset, y + glyph.yoffset),
+                        glyph,
+                    });
+                }
+                x += glyph.xadvance;
+                previous = Some(c);
+            }
+        }
+
+        placed
+    }
+}
+
+// the tag and `key=value` pairs of a line, with quotes taken off values
+fn parse_line(line: &str) -> Result<(&str, HashMap<&str, &str>), String> {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    let mut values = HashMap::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        let (key, after_key) = rest.split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {:?}", rest))?;
+        let (value, after_value) = match after_key.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"')
+                .ok_or_else(|| format!("unterminated quote in {}", key))?,
+            None => after_key.split_once(char::is_whitespace).unwrap_or((after_key, "")),
+        };
+        values.insert(key, value);
+        rest = after_value;
+    }
+
+    Ok((tag, values))
+}
+
+// the words of `text` and where they start, split at spaces
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(' ')
+        .scan(0, |start, word| {
+            let word_start = *start;
+            *start += word.len() + 1;
+            Some((word_start, word))
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
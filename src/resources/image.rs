@@ -0,0 +1,69 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// RGBA8 pixels, rows top to bottom.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Decodes a png of any format, see `gfx::decode_png`.
+    pub fn decode_png<R: Read>(input: R) -> io::Result<Image> {
+        let (width, height, pixels) = crate::gfx::decode_png(input).map_err(invalid_data)?;
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn encode_png<W: Write>(&self, output: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(output, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(invalid_data)?;
+        writer.write_image_data(&self.pixels).map_err(invalid_data)
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        Self::decode_png(io::BufReader::new(fs::File::open(path)?))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.encode_png(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Reads back the bottom-left `width` by `height` pixels of the bound
+    /// read framebuffer.
+    pub fn read_framebuffer(width: u32, height: u32) -> Image {
+        let mut pixels = vec![0u8; 4*(width*height) as usize];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+        }
+
+        // opengl rows go bottom to top
+        let row = 4*width as usize;
+        let pixels = pixels.chunks_exact(row).rev().flatten().copied().collect();
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> &[u8] {
+        let i = 4*(y*self.width + x) as usize;
+        &self.pixels[i..i + 4]
+    }
+}
+
+pub fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,130 @@
+//! The level's lights, as seen by `lighting.glsl`.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector2, Vector3, Vector4};
+
+use crate::c_str;
+use crate::gfx::*;
+use crate::level::{Lantern, Lighting};
+
+/// How many lanterns the shaders take into account, as in `lighting.glsl`.
+pub const MAX_LANTERNS: usize = 8;
+/// Where the shadow map is bound while lit programs draw. Unit 0 is left to
+/// the atlas.
+pub const SHADOW_MAP_UNIT: u32 = 1;
+
+/// Light values ready to upload.
+#[derive(Clone, Copy)]
+pub struct Lights {
+    ambient: Vector3<f32>,
+    sun_direction: Vector3<f32>,
+    sun_color: Vector3<f32>,
+    lantern_count: i32,
+    // position and radius
+    lanterns: [Vector4<f32>; MAX_LANTERNS],
+    lantern_colors: [Vector4<f32>; MAX_LANTERNS],
+    fog_color: Vector3<f32>,
+    fog_range: Vector2<f32>,
+    fog_density: f32,
+}
+
+impl Lights {
+    /// Only the first `MAX_LANTERNS` lanterns are kept.
+    pub fn new(lighting: &Lighting, lanterns: &[Lantern]) -> Lights {
+        super::warn_over_limit("lanterns", lanterns.len(), MAX_LANTERNS);
+
+        let mut lights = Lights {
+            ambient: lighting.ambient.into(),
+            sun_direction: Vector3::from(lighting.sun_direction).normalize(),
+            sun_color: lighting.sun_color.into(),
+            lantern_count: lanterns.len().min(MAX_LANTERNS) as i32,
+            lanterns: [Vector4::zero(); MAX_LANTERNS],
+            lantern_colors: [Vector4::zero(); MAX_LANTERNS],
+            fog_color: lighting.fog.color.into(),
+            fog_range: Vector2::new(lighting.fog.start, lighting.fog.end),
+            fog_density: lighting.fog.density,
+        };
+        for (i, lantern) in lanterns.iter().take(MAX_LANTERNS).enumerate() {
+            lights.lanterns[i] = Vector3::from(lantern.position).extend(lantern.radius);
+            lights.lantern_colors[i] = Vector3::from(lantern.color).extend(1.0);
+        }
+
+        lights
+    }
+
+    /// The sun's view and projection, fitted around the box from `min` to
+    /// `max`, for rendering and sampling the shadow map.
+    pub fn light_matrix(&self, min: Vector3<f32>, max: Vector3<f32>) -> Matrix4<f32> {
+        // any up will do, as long as it isn't along the sunlight
+        let up = if self.sun_direction.z.abs() < 0.99 { Vector3::unit_z() } else { Vector3::unit_x() };
+        let view = Matrix4::look_at_dir(Point3::new(0.0, 0.0, 0.0), self.sun_direction, up);
+
+        let mut low = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut high = -low;
+        for i in 0..8 {
+            let corner = Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            let corner = (view * corner.extend(1.0)).truncate();
+            low = Vector3::new(low.x.min(corner.x), low.y.min(corner.y), low.z.min(corner.z));
+            high = Vector3::new(high.x.max(corner.x), high.y.max(corner.y), high.z.max(corner.z));
+        }
+
+        // the sun looks down its -z, with a little room in front and behind
+        cgmath::ortho(low.x, high.x, low.y, high.y, -high.z - 1.0, -low.z + 1.0) * view
+    }
+}
+
+/// The uniforms of `lighting.glsl`, in one program.
+pub struct LightingUniforms {
+    ambient: Uniform<Vector3<f32>>,
+    sun_direction: Uniform<Vector3<f32>>,
+    sun_color: Uniform<Vector3<f32>>,
+    lantern_count: Uniform<i32>,
+    lanterns: Uniform<[Vector4<f32>; MAX_LANTERNS]>,
+    lantern_colors: Uniform<[Vector4<f32>; MAX_LANTERNS]>,
+    eye_direction: Uniform<Vector3<f32>>,
+    shadow_map: Uniform<TextureUnit>,
+    light_matrix: Uniform<Matrix4<f32>>,
+    fog_color: Uniform<Vector3<f32>>,
+    fog_range: Uniform<Vector2<f32>>,
+    fog_density: Uniform<f32>,
+}
+
+impl LightingUniforms {
+    pub fn new(program: &ShaderProgram) -> Result<LightingUniforms, Error> {
+        Ok(LightingUniforms {
+            ambient: program.uniform(c_str!("ambient"))?,
+            sun_direction: program.uniform(c_str!("sun_direction"))?,
+            sun_color: program.uniform(c_str!("sun_color"))?,
+            lantern_count: program.uniform(c_str!("lantern_count"))?,
+            lanterns: program.uniform(c_str!("lanterns"))?,
+            lantern_colors: program.uniform(c_str!("lantern_colors"))?,
+            eye_direction: program.uniform(c_str!("eye_direction"))?,
+            shadow_map: program.uniform(c_str!("shadow_map"))?,
+            light_matrix: program.uniform(c_str!("light_matrix"))?,
+            fog_color: program.uniform(c_str!("fog_color"))?,
+            fog_range: program.uniform(c_str!("fog_range"))?,
+            fog_density: program.uniform(c_str!("fog_density"))?,
+        })
+    }
+
+    /// `eye_direction` points towards the camera, in world space, and
+    /// `light_matrix` is what the shadow map was rendered with.
+    pub fn set(&self, program: &BoundProgram, lights: &Lights, eye_direction: Vector3<f32>, light_matrix: Matrix4<f32>) {
+        program.set(&self.ambient, lights.ambient);
+        program.set(&self.sun_direction, lights.sun_direction);
+        program.set(&self.sun_color, lights.sun_color);
+        program.set(&self.lantern_count, lights.lantern_count);
+        program.set(&self.lanterns, lights.lanterns);
+        program.set(&self.lantern_colors, lights.lantern_colors);
+        program.set(&self.eye_direction, eye_direction);
+        program.set(&self.shadow_map, TextureUnit(SHADOW_MAP_UNIT));
+        program.set(&self.light_matrix, light_matrix);
+        program.set(&self.fog_color, lights.fog_color);
+        program.set(&self.fog_range, lights.fog_range);
+        program.set(&self.fog_density, lights.fog_density);
+    }
+}
@@ -0,0 +1,124 @@
+//! Lines in world or screen space, for debugging. Like text, they are
+//! queued and then drawn together by `flush`, over everything else.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Vector3};
+
+use std::cell::{Cell, RefCell};
+
+use super::shader_sources::ShaderSources;
+use crate::c_str;
+use crate::gfx::*;
+use crate::vertex_layout;
+
+const STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "lines.vert"), (ShaderType::Fragment, "lines.frag")];
+
+vertex_layout! {
+    struct LineVertex {
+        position: [f32; 3] = "inPosition",
+        color: [f32; 3] = "inColor",
+    }
+}
+
+pub struct LineRenderer {
+    program: ShaderProgram,
+    unif_view: Uniform<Matrix4<f32>>,
+    unif_proj: Uniform<Matrix4<f32>>,
+
+    mesh: RefCell<Mesh<LineVertex>>,
+    // two vertices per line, since the last flush
+    queued: RefCell<Vec<LineVertex>>,
+    // the same in pixels, with z zero
+    screen_queued: RefCell<Vec<LineVertex>>,
+    // view and projection of world-space lines
+    camera: Cell<(Matrix4<f32>, Matrix4<f32>)>,
+}
+
+impl LineRenderer {
+    pub fn new(shaders: &ShaderSources) -> Result<LineRenderer, Error> {
+        let program = shaders.build_program("lines", STAGES, &[])?;
+        let unif_view = program.uniform(c_str!("view"))?;
+        let unif_proj = program.uniform(c_str!("proj"))?;
+
+        let mesh = Mesh::new(&program, gl::LINES, &[])?;
+        mesh.set_label("lines");
+
+        Ok(LineRenderer {
+            program,
+            unif_view,
+            unif_proj,
+
+            mesh: RefCell::new(mesh),
+            queued: RefCell::new(Vec::new()),
+            screen_queued: RefCell::new(Vec::new()),
+            camera: Cell::new((Matrix4::identity(), Matrix4::identity())),
+        })
+    }
+
+    pub fn reload_shaders(&self, shaders: &ShaderSources, changed: &[String]) {
+        shaders.reload(&self.program, "lines", STAGES, &[], changed);
+    }
+
+    pub fn set_camera_matrices(&self, view: Matrix4<f32>, proj: Matrix4<f32>) {
+        self.camera.set((view, proj));
+    }
+
+    /// Queues a line from `from` to `to`, to be drawn by the next `flush`.
+    pub fn line(&self, from: Vector3<f32>, to: Vector3<f32>, color: (f32, f32, f32)) {
+        let color = color.into();
+        self.queued.borrow_mut().extend_from_slice(&[
+            LineVertex { position: from.into(), color },
+            LineVertex { position: to.into(), color },
+        ]);
+    }
+
+    /// Like `line`, in pixels from the top left corner of the viewport.
+    pub fn screen_line(&self, from: (f32, f32), to: (f32, f32), color: (f32, f32, f32)) {
+        let color = color.into();
+        self.screen_queued.borrow_mut().extend_from_slice(&[
+            LineVertex { position: [from.0, from.1, 0.0], color },
+            LineVertex { position: [to.0, to.1, 0.0], color },
+        ]);
+    }
+
+    /// Queues a closed loop through `corners`.
+    pub fn outline(&self, corners: &[Vector3<f32>], color: (f32, f32, f32)) {
+        for (i, &corner) in corners.iter().enumerate() {
+            self.line(corner, corners[(i + 1) % corners.len()], color);
+        }
+    }
+
+    /// Draws and forgets everything queued, ignoring depth. World-space
+    /// lines go first.
+    pub fn flush(&self) {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        let pixels = cgmath::ortho(0.0, viewport[2] as f32, viewport[3] as f32, 0.0, -1.0, 1.0);
+
+        let (view, proj) = self.camera.get();
+        self.draw(&mut self.queued.borrow_mut(), view, proj);
+        self.draw(&mut self.screen_queued.borrow_mut(), Matrix4::identity(), pixels);
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+
+    fn draw(&self, queued: &mut Vec<LineVertex>, view: Matrix4<f32>, proj: Matrix4<f32>) {
+        if queued.is_empty() {
+            return;
+        }
+
+        let mut mesh = self.mesh.borrow_mut();
+        mesh.vertices_mut().set_data(queued);
+        queued.clear();
+
+        let program = self.program.bind();
+        program.set(&self.unif_view, view);
+        program.set(&self.unif_proj, proj);
+        mesh.draw();
+    }
+}
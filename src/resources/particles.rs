@@ -0,0 +1,110 @@
+//! Particles as camera-facing squares, all of them in one instanced draw.
+
+use cgmath::Matrix4;
+
+use std::cell::RefCell;
+
+use super::shader_sources::ShaderSources;
+use super::vertex_data::{self, SquareVertex};
+use crate::c_str;
+use crate::gfx::*;
+use crate::particles::Particle;
+use crate::vertex_layout;
+
+const STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "particle.vert"), (ShaderType::Fragment, "particle.frag")];
+
+vertex_layout! {
+    struct ParticleInstance {
+        center: [f32; 3] = "inCenter",
+        size: f32 = "inSize",
+        color: [f32; 4] = "inColor",
+    }
+}
+
+pub struct ParticleRenderer {
+    program: ShaderProgram,
+    unif_view: Uniform<Matrix4<f32>>,
+    unif_proj: Uniform<Matrix4<f32>>,
+    unif_billboard: Uniform<Matrix4<f32>>,
+
+    square: Mesh<SquareVertex>,
+    instances: RefCell<Buffer<ParticleInstance>>,
+}
+
+impl ParticleRenderer {
+    pub fn new(shaders: &ShaderSources) -> Result<ParticleRenderer, Error> {
+        let program = shaders.build_program("particle", STAGES, &[])?;
+        let unif_view = program.uniform(c_str!("view"))?;
+        let unif_proj = program.uniform(c_str!("proj"))?;
+        let unif_billboard = program.uniform(c_str!("billboard"))?;
+
+        let square = Mesh::indexed(&program, gl::TRIANGLES, vertex_data::SQUARE_VERTICES, vertex_data::SQUARE_INDICES)?;
+        let instances = Buffer::new(BufferTarget::Array, BufferUsage::Stream);
+        square.set_instances(&program, &instances)?;
+        square.set_label("particles");
+        instances.set_label("particle instances");
+
+        Ok(ParticleRenderer {
+            program,
+            unif_view,
+            unif_proj,
+            unif_billboard,
+
+            square,
+            instances: RefCell::new(instances),
+        })
+    }
+
+    pub fn reload_shaders(&self, shaders: &ShaderSources, changed: &[String]) {
+        shaders.reload(&self.program, "particle", STAGES, &[], changed);
+    }
+
+    /// `inverse_rotation` undoes the camera's turn about z, like for
+    /// sprites.
+    pub fn set_camera_matrices(&self, view: Matrix4<f32>, proj: Matrix4<f32>, inverse_rotation: Matrix4<f32>) {
+        let program = self.program.bind();
+        program.set(&self.unif_view, view);
+        program.set(&self.unif_proj, proj);
+        // the square lies in xy, stand it up first
+        program.set(&self.unif_billboard, inverse_rotation * Matrix4::from_angle_x(cgmath::Deg(90.0)));
+    }
+
+    /// Blends the particles over what's drawn, hidden by anything in front
+    /// of them but not hiding each other.
+    pub fn draw(&self, particles: &[Particle]) {
+        if particles.is_empty() {
+            return;
+        }
+
+        let data = particles.iter()
+            .map(|particle| {
+                let color = particle.color();
+                ParticleInstance {
+                    center: particle.position.into(),
+                    size: particle.size(),
+                    color: [color.0, color.1, color.2, color.3],
+                }
+            })
+            .collect::<Vec<_>>();
+        // reuses the storage unless there are more particles than ever before
+        let mut instances = self.instances.borrow_mut();
+        if data.len() <= instances.len() {
+            instances.update(0, &data);
+        } else {
+            instances.set_data(&data);
+        }
+        drop(instances);
+
+        let _program = self.program.bind();
+        unsafe {
+            gl::DepthMask(gl::FALSE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        self.square.draw_instanced(data.len());
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::DepthMask(gl::TRUE);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Fullscreen effects applied to the finished frame.
+//!
+//! While any pass is enabled, the scene is drawn into an off-screen target.
+//! Each enabled pass then draws a fullscreen triangle sampling the previous
+//! result, ping-ponging between two more targets, and the last one draws
+//! into whatever framebuffer was bound when the frame began.
+
+use gl::types::*;
+use cgmath::Vector2;
+
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+
+use super::ShaderSources;
+use crate::gfx::*;
+use crate::c_str;
+use crate::settings::Settings;
+
+/// A named float uniform of a pass, adjustable at runtime.
+struct Param {
+    name: String,
+    value: f32,
+    uniform: Uniform<f32>,
+}
+
+pub struct Pass {
+    name: String,
+    fragment: String,
+    program: ShaderProgram,
+    enabled: bool,
+
+    unif_resolution: Uniform<Vector2<f32>>,
+    params: Vec<Param>,
+}
+
+impl Pass {
+    /// Compiles a pass from the fragment shader `{name}.frag`, which gets
+    /// the previous result as `uniform sampler2D scene`, its size in pixels
+    /// as `uniform vec2 resolution` and `TexCoords` from the vertex shader.
+    /// Every param is a float uniform with the given default. Passes start
+    /// out disabled.
+    pub fn new(shaders: &ShaderSources, name: &str, params: &[(&str, f32)]) -> Result<Pass, Error> {
+        let fragment = format!("{}.frag", name);
+        let program = shaders.build_program(&format!("{} pass", name), &[
+            (ShaderType::Vertex, "fullscreen.vert"),
+            (ShaderType::Fragment, &fragment),
+        ], &[])?;
+
+        // unlike the other programs, params the shader doesn't use are fine
+        let params = params.iter()
+            .map(|&(name, value)| {
+                let c_name = CString::new(name).expect("param names can't contain nul bytes");
+
+                Param {
+                    name: name.to_string(),
+                    value,
+                    uniform: program.optional_uniform(&c_name),
+                }
+            })
+            .collect();
+
+        // not every pass needs it either
+        let unif_resolution = program.optional_uniform(c_str!("resolution"));
+
+        Ok(Pass {
+            name: name.to_string(),
+            fragment,
+            program,
+            enabled: false,
+
+            unif_resolution,
+            params,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn param(&self, name: &str) -> Option<f32> {
+        self.params.iter().find(|param| param.name == name).map(|param| param.value)
+    }
+
+    /// Returns false if the pass has no such param.
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match self.params.iter_mut().find(|param| param.name == name) {
+            Some(param) => {
+                param.value = value;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Every param name and its current value, in declaration order.
+    pub fn params(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.params.iter().map(|param| (param.name.as_str(), param.value))
+    }
+
+    fn reload(&self, shaders: &ShaderSources, changed: &[String]) {
+        shaders.reload(&self.program, &format!("{} pass", self.name), &[
+            (ShaderType::Vertex, "fullscreen.vert"),
+            (ShaderType::Fragment, &self.fragment),
+        ], &[], changed);
+    }
+
+    fn draw(&self, input: &Texture, width: u32, height: u32) {
+        let program = self.program.bind();
+        program.set(&self.unif_resolution, Vector2::new(width as f32, height as f32));
+        for param in &self.params {
+            program.set(&param.uniform, param.value);
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, input.name());
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+}
+
+struct Targets {
+    scene: RenderTarget,
+    ping_pong: [RenderTarget; 2],
+}
+
+impl Targets {
+    fn new(width: u32, height: u32) -> Result<Targets, Error> {
+        Ok(Targets {
+            scene: RenderTarget::new(width, height, 1, true)?,
+            ping_pong: [
+                RenderTarget::new(width, height, 1, false)?,
+                RenderTarget::new(width, height, 1, false)?,
+            ],
+        })
+    }
+}
+
+pub struct PostProcessor {
+    passes: Vec<Pass>,
+
+    // core profile wants a vao bound even without attributes
+    vao: VertexArrayObject,
+    // created on first use and whenever the viewport size changes
+    targets: RefCell<Option<Targets>>,
+    // the framebuffer and viewport to draw the last pass into, while a
+    // frame is in progress
+    output: Cell<Option<(GLuint, [GLint; 4])>>,
+    // set once the targets couldn't be created, after which frames are
+    // drawn without post-processing
+    unavailable: Cell<bool>,
+}
+
+impl PostProcessor {
+    /// Sets up the built-in passes, all disabled: pixelate, bloom,
+    /// color_grade, scanlines and vignette, applied in that order.
+    pub fn new(shaders: &ShaderSources) -> Result<PostProcessor, Error> {
+        let passes = vec![
+            Pass::new(shaders, "pixelate", &[
+                ("size", 4.0),
+            ]),
+            Pass::new(shaders, "bloom", &[
+                ("threshold", 0.7),
+                ("intensity", 0.8),
+                ("radius", 8.0),
+            ]),
+            Pass::new(shaders, "color_grade", &[
+                ("exposure", 1.0),
+                ("contrast", 1.1),
+                ("saturation", 1.2),
+                ("tint_r", 1.0),
+                ("tint_g", 1.0),
+                ("tint_b", 1.0),
+            ]),
+            Pass::new(shaders, "scanlines", &[
+                ("intensity", 0.25),
+                ("spacing", 3.0),
+            ]),
+            Pass::new(shaders, "vignette", &[
+                ("strength", 0.6),
+                ("radius", 0.8),
+                ("softness", 0.8),
+            ]),
+        ];
+
+        Ok(PostProcessor {
+            passes: passes.into_iter().collect::<Result<_, _>>()?,
+
+            vao: {
+                let vao = VertexArrayObject::new();
+                vao.set_label("fullscreen triangle");
+                vao
+            },
+            targets: RefCell::new(None),
+            output: Cell::new(None),
+            unavailable: Cell::new(false),
+        })
+    }
+
+    /// Appends a pass, to be applied after the existing ones.
+    pub fn add_pass(&mut self, pass: Pass) {
+        self.passes.push(pass);
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    pub fn passes_mut(&mut self) -> &mut [Pass] {
+        &mut self.passes
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut Pass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    /// Recompiles the passes using any of the `changed` sources.
+    pub fn reload_shaders(&self, shaders: &ShaderSources, changed: &[String]) {
+        for pass in &self.passes {
+            pass.reload(shaders, changed);
+        }
+    }
+
+    /// Reads one section per pass, named after it, holding `enabled` and
+    /// any of its params. Missing keys keep their current value.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        for pass in &mut self.passes {
+            if let Some(enabled) = settings.get_parsed(&pass.name, "enabled") {
+                pass.enabled = enabled;
+            }
+
+            for (key, _) in settings.section(&pass.name) {
+                if key == "enabled" {
+                    continue;
+                }
+
+                match settings.get_parsed(&pass.name, key) {
+                    Some(value) if !pass.set_param(key, value) => {
+                        log::warn!("settings: {} pass has no param {:?}", pass.name, key);
+                    },
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Redirects drawing into the scene target, if any pass is enabled.
+    /// Must be paired with `finish`. If the target can't be created, drawing
+    /// stays in the current framebuffer and post-processing is off from
+    /// then on.
+    pub fn begin(&self) -> Result<(), Error> {
+        if self.unavailable.get() || !self.passes.iter().any(Pass::enabled) {
+            return Ok(());
+        }
+
+        let mut viewport = [0; 4];
+        let mut framebuffer = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut framebuffer);
+        }
+        let (width, height) = (viewport[2] as u32, viewport[3] as u32);
+
+        let mut targets = self.targets.borrow_mut();
+        let stale = targets.as_ref()
+            .is_none_or(|targets| (targets.scene.width(), targets.scene.height()) != (width, height));
+        if stale {
+            let new = Targets::new(width, height).inspect_err(|_| self.unavailable.set(true))?;
+            new.scene.set_label("post-processing scene");
+            new.ping_pong[0].set_label("post-processing ping");
+            new.ping_pong[1].set_label("post-processing pong");
+
+            *targets = Some(new);
+        }
+
+        targets.as_ref().unwrap().scene.bind();
+        self.output.set(Some((framebuffer as GLuint, viewport)));
+
+        Ok(())
+    }
+
+    /// Runs the enabled passes over what was drawn since `begin`, into the
+    /// framebuffer that was bound back then.
+    pub fn finish(&self) {
+        let (framebuffer, viewport) = match self.output.take() {
+            Some(output) => output,
+            None => return,
+        };
+
+        let targets = self.targets.borrow();
+        let targets = targets.as_ref().unwrap();
+        let (width, height) = (targets.scene.width(), targets.scene.height());
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        self.vao.bind();
+
+        let enabled: Vec<&Pass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+        targets.scene.resolve();
+        let mut input = targets.scene.color_texture();
+        for (i, pass) in enabled.iter().enumerate() {
+            let _group = debug::group(&pass.name);
+            if i + 1 == enabled.len() {
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                    gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+                }
+                pass.draw(input, width, height);
+            } else {
+                let output = &targets.ping_pong[i % 2];
+                output.bind();
+                pass.draw(input, width, height);
+                input = output.color_texture();
+            }
+        }
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
@@ -1,72 +1,175 @@
-/*pub static TEXTURED_VERTEX_SHADER: &str = r#"
-#version 150 core
-
-in vec3 inPosition;
-in vec2 inTexCoords;
-
-out vec2 TexCoords;
-
-uniform mat4 model;
-uniform mat4 model_inv;
-uniform mat4 view;
-uniform mat4 proj;
-
-void main()
-{
-        gl_Position = proj * view * model * vec4(inPosition, 1.0);
-        TexCoords = inTexCoords;
+//! Shader sources, by file name. They are embedded into the binary, or in
+//! dev mode read from `SHADER_DIR`, so they can be edited while the game
+//! runs.
+//!
+//! Sources go through `gfx::preprocess`, so they can `#include` shared code
+//! such as `lighting.glsl`.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::gfx::*;
+
+/// Where the shader files live in the source tree.
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+static EMBEDDED: &[(&str, &str)] = &[
+    ("solid.vert", include_str!("../shaders/solid.vert")),
+    ("solid.frag", include_str!("../shaders/solid.frag")),
+    ("lighting.glsl", include_str!("../shaders/lighting.glsl")),
+    ("shadow.frag", include_str!("../shaders/shadow.frag")),
+    ("blob.frag", include_str!("../shaders/blob.frag")),
+    ("silhouette.frag", include_str!("../shaders/silhouette.frag")),
+    ("text.vert", include_str!("../shaders/text.vert")),
+    ("text.frag", include_str!("../shaders/text.frag")),
+    ("lines.vert", include_str!("../shaders/lines.vert")),
+    ("lines.frag", include_str!("../shaders/lines.frag")),
+    ("particle.vert", include_str!("../shaders/particle.vert")),
+    ("particle.frag", include_str!("../shaders/particle.frag")),
+    ("fullscreen.vert", include_str!("../shaders/fullscreen.vert")),
+    ("background.frag", include_str!("../shaders/background.frag")),
+    ("pixelate.frag", include_str!("../shaders/pixelate.frag")),
+    ("bloom.frag", include_str!("../shaders/bloom.frag")),
+    ("color_grade.frag", include_str!("../shaders/color_grade.frag")),
+    ("scanlines.frag", include_str!("../shaders/scanlines.frag")),
+    ("vignette.frag", include_str!("../shaders/vignette.frag")),
+];
+
+/// Where shader sources come from.
+pub struct ShaderSources {
+    // read files from here instead of using the embedded copies
+    dir: Option<PathBuf>,
+    // modification time of every file read so far, None if it couldn't be
+    // read
+    mtimes: RefCell<HashMap<String, Option<SystemTime>>>,
+    // what each shader included when it was last compiled
+    includes: RefCell<HashMap<String, Vec<String>>>,
 }
-"#;
-
-pub static TEXTURED_FRAGMENT_SHADER: &str = r#"
-#version 150 core
 
-in vec2 TexCoords;
-
-out vec4 outColor;
-
-uniform sampler2D tex;
+impl ShaderSources {
+    /// The copies built into the binary.
+    pub fn embedded() -> ShaderSources {
+        ShaderSources {
+            dir: None,
+            mtimes: RefCell::new(HashMap::new()),
+            includes: RefCell::new(HashMap::new()),
+        }
+    }
 
-void main()
-{
-        outColor = texture(tex, TexCoords * (1.0, -1.0));
-}
-"#;*/
+    /// Reads every source from a file in `dir`, and keeps track of changes
+    /// to them.
+    pub fn from_dir<P: Into<PathBuf>>(dir: P) -> ShaderSources {
+        ShaderSources {
+            dir: Some(dir.into()),
+            mtimes: RefCell::new(HashMap::new()),
+            includes: RefCell::new(HashMap::new()),
+        }
+    }
 
-pub static SOLID_VERTEX_SHADER: &str = r#"
-#version 150 core
+    pub fn get(&self, name: &str) -> Result<Cow<'static, str>, Error> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return EMBEDDED.iter()
+                .find(|(file, _)| *file == name)
+                .map(|(_, source)| Cow::Borrowed(*source))
+                .ok_or_else(|| Error::ReadSource {
+                    path: name.into(),
+                    error: std::io::ErrorKind::NotFound.into(),
+                }),
+        };
+
+        let path = dir.join(name);
+        self.mtimes.borrow_mut().insert(name.to_string(), modified(&path));
+        std::fs::read_to_string(&path)
+            .map(Cow::Owned)
+            .map_err(|error| Error::ReadSource { path, error })
+    }
 
-in vec3 inPosition;
-in vec3 inNormal;
+    /// Builds a program from one source per stage, named by `program_name`
+    /// in errors. Every stage gets the `defines`, to pick a variant.
+    pub fn build_program(&self, program_name: &str, stages: &[(ShaderType, &str)], defines: &[(&str, &str)]) -> Result<ShaderProgram, Error> {
+        self.build(program_name, stages, defines, None)
+    }
 
-out vec3 Normal;
+    /// Like `build_program`, but attributes get the locations they have in
+    /// `other`, so both programs can draw the same meshes.
+    pub fn build_program_like(&self, other: &ShaderProgram, program_name: &str, stages: &[(ShaderType, &str)], defines: &[(&str, &str)]) -> Result<ShaderProgram, Error> {
+        self.build(program_name, stages, defines, Some(other))
+    }
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 proj;
+    fn build(&self, program_name: &str, stages: &[(ShaderType, &str)], defines: &[(&str, &str)], like: Option<&ShaderProgram>) -> Result<ShaderProgram, Error> {
+        let shaders = self.compile_all(stages, defines)?;
 
-void main() {
-    gl_Position = proj * view * model * vec4(inPosition, 1.0);
-    Normal = inNormal * inverse(mat3(view * model));
-}
-"#;
+        let program = ShaderProgram::new();
+        for shader in &shaders {
+            program.attach(shader);
+        }
+        if let Some(other) = like {
+            program.share_attrib_locations(other);
+        }
+        program.link(program_name)?;
 
-pub static SOLID_FRAGMENT_SHADER: &str = r#"
-#version 150
+        Ok(program)
+    }
 
-in vec3 Normal;
+    /// Rebuilds `program` if any of its `stages`, or a file they include, is
+    /// in `changed`. On failure the error is printed and the program keeps
+    /// working as before.
+    pub fn reload(&self, program: &ShaderProgram, program_name: &str, stages: &[(ShaderType, &str)], defines: &[(&str, &str)], changed: &[String]) {
+        let affected = {
+            let includes = self.includes.borrow();
+            stages.iter()
+                .flat_map(|(_, name)| std::iter::once(*name).chain(includes.get(*name).into_iter().flatten().map(String::as_str)))
+                .any(|name| changed.iter().any(|changed| changed == name))
+        };
+        if !affected {
+            return;
+        }
+
+        let result = self.compile_all(stages, defines)
+            .and_then(|shaders| program.relink(&shaders.iter().collect::<Vec<_>>(), program_name));
+        match result {
+            Ok(()) => log::info!("reloaded {} program", program_name),
+            Err(err) => log::error!("{}", err),
+        }
+    }
 
-out vec4 outColor;
+    fn compile_all(&self, stages: &[(ShaderType, &str)], defines: &[(&str, &str)]) -> Result<Vec<Shader>, Error> {
+        stages.iter()
+            .map(|&(type_, name)| {
+                let preprocessed = preprocess(name, defines, |file| self.get(file))?;
+                self.includes.borrow_mut().insert(name.to_string(), preprocessed.files[1..].to_vec());
 
-uniform vec3 color;
-uniform bool apply_diffuse;
+                Shader::compile(type_, name, &preprocessed.source)
+                    .map_err(|err| preprocessed.map_error(err))
+            })
+            .collect()
+    }
 
-void main() {
-    float diffuse = 1.0;
-    if (apply_diffuse) {
-        diffuse = max(normalize(Normal).z, 0.0);
+    /// Names of the files modified since they were last read or reported
+    /// here. Always empty for embedded sources.
+    pub fn changed(&self) -> Vec<String> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Vec::new(),
+        };
+
+        let mut changed = Vec::new();
+        for (name, mtime) in self.mtimes.borrow_mut().iter_mut() {
+            let current = modified(&dir.join(name));
+            if current != *mtime {
+                *mtime = current;
+                changed.push(name.clone());
+            }
+        }
+
+        changed
     }
+}
 
-    outColor = vec4(vec3(0.5 + 0.5*diffuse)*color, 1.0);
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
 }
-"#;
@@ -0,0 +1,175 @@
+//! Screen-space text, drawn over everything else. Strings are queued and
+//! then drawn together by `flush`, in one draw call.
+
+use cgmath::Vector2;
+
+use std::cell::RefCell;
+
+use super::font::{Align, Font, Layout};
+use super::shader_sources::ShaderSources;
+use crate::c_str;
+use crate::gfx::*;
+use crate::vertex_layout;
+
+const FONT_NAME: &str = "pixel.fnt";
+const FONT_SOURCE: &str = include_str!("../fonts/pixel.fnt");
+// the page FONT_SOURCE names
+const FONT_PAGE: &[u8] = include_bytes!("../fonts/pixel.png");
+
+const STAGES: &[(ShaderType, &str)] = &[(ShaderType::Vertex, "text.vert"), (ShaderType::Fragment, "text.frag")];
+
+vertex_layout! {
+    struct TextVertex {
+        position: [f32; 2] = "inPosition",
+        tex_coords: [f32; 2] = "inTexCoords",
+        color: [f32; 4] = "inColor",
+    }
+}
+
+/// How `TextRenderer::queue` draws a string.
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    /// Screen pixels per font pixel. Whole numbers keep pixel fonts crisp.
+    pub scale: f32,
+    pub color: (f32, f32, f32, f32),
+    pub align: Align,
+    /// In screen pixels.
+    pub max_width: Option<f32>,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            scale: 2.0,
+            color: (1.0, 1.0, 1.0, 1.0),
+            align: Align::Left,
+            max_width: None,
+        }
+    }
+}
+
+pub struct TextRenderer {
+    font: Font,
+    texture: Texture,
+
+    program: ShaderProgram,
+    unif_screen_size: Uniform<Vector2<f32>>,
+    unif_font: Uniform<TextureUnit>,
+
+    mesh: RefCell<Mesh<TextVertex>>,
+    // two triangles per glyph, since the last flush
+    queued: RefCell<Vec<TextVertex>>,
+}
+
+impl TextRenderer {
+    pub fn new(shaders: &ShaderSources) -> Result<TextRenderer, Error> {
+        let font = Font::parse(FONT_SOURCE)
+            .map_err(|message| Error::FontParse { source: FONT_NAME.to_string(), message })?;
+        let texture = Texture::load_from_png(FONT_PAGE, &TextureOptions {
+            wrap: Wrap::ClampToEdge,
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            mipmaps: false,
+            ..TextureOptions::default()
+        })?;
+        texture.set_label("font");
+
+        let program = shaders.build_program("text", STAGES, &[])?;
+        let unif_screen_size = program.uniform(c_str!("screen_size"))?;
+        let unif_font = program.uniform(c_str!("font"))?;
+
+        let mesh = Mesh::new(&program, gl::TRIANGLES, &[])?;
+        mesh.set_label("text");
+
+        Ok(TextRenderer {
+            font,
+            texture,
+
+            program,
+            unif_screen_size,
+            unif_font,
+
+            mesh: RefCell::new(mesh),
+            queued: RefCell::new(Vec::new()),
+        })
+    }
+
+    pub fn reload_shaders(&self, shaders: &ShaderSources, changed: &[String]) {
+        shaders.reload(&self.program, "text", STAGES, &[], changed);
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Size of the viewport, which text is positioned in.
+    pub fn screen_size(&self) -> (f32, f32) {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+
+        (viewport[2] as f32, viewport[3] as f32)
+    }
+
+    /// Lays out `text` at `position`, in pixels from the top left corner of
+    /// the viewport, to be drawn by the next `flush`.
+    pub fn queue(&self, text: &str, position: (f32, f32), style: &TextStyle) {
+        let layout = Layout {
+            align: style.align,
+            max_width: style.max_width.map(|max_width| max_width / style.scale),
+        };
+        let (page_width, page_height) = (self.font.page_size.0 as f32, self.font.page_size.1 as f32);
+        let color = [style.color.0, style.color.1, style.color.2, style.color.3];
+
+        let mut queued = self.queued.borrow_mut();
+        for placed in self.font.layout(text, &layout) {
+            let glyph = placed.glyph;
+            // snapped to whole pixels, so texels line up with them
+            let x0 = (position.0 + placed.position.0*style.scale).round();
+            let y0 = (position.1 + placed.position.1*style.scale).round();
+            let (x1, y1) = (x0 + glyph.width as f32*style.scale, y0 + glyph.height as f32*style.scale);
+
+            let u0 = glyph.x as f32 / page_width;
+            let v0 = glyph.y as f32 / page_height;
+            let u1 = (glyph.x + glyph.width) as f32 / page_width;
+            let v1 = (glyph.y + glyph.height) as f32 / page_height;
+
+            let vertex = |x, y, u, v| TextVertex { position: [x, y], tex_coords: [u, v], color };
+            queued.extend_from_slice(&[
+                vertex(x0, y0, u0, v0), vertex(x0, y1, u0, v1), vertex(x1, y0, u1, v0),
+                vertex(x1, y0, u1, v0), vertex(x0, y1, u0, v1), vertex(x1, y1, u1, v1),
+            ]);
+        }
+    }
+
+    /// Draws and forgets everything queued, over whatever is in the
+    /// framebuffer.
+    pub fn flush(&self) {
+        let mut queued = self.queued.borrow_mut();
+        if queued.is_empty() {
+            return;
+        }
+
+        let mut mesh = self.mesh.borrow_mut();
+        mesh.vertices_mut().set_data(&queued);
+        queued.clear();
+
+        let (width, height) = self.screen_size();
+        let program = self.program.bind();
+        program.set(&self.unif_screen_size, Vector2::new(width, height));
+        program.set(&self.unif_font, TextureUnit(0));
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.texture.name());
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        mesh.draw();
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
@@ -1,82 +1,118 @@
-use gl::types::*;
+use crate::vertex_layout;
+
+vertex_layout! {
+    pub struct SquareVertex {
+        pub position: [f32; 3] = "inPosition",
+        pub tex_coords: [f32; 2] = "inTexCoords",
+    }
+}
+
+vertex_layout! {
+    pub struct PlatformVertex {
+        pub position: [f32; 3] = "inPosition",
+        pub normal: [f32; 3] = "inNormal",
+        pub tex_coords: [f32; 2] = "inTexCoords",
+    }
+}
+
+vertex_layout! {
+    pub struct EdgeVertex {
+        pub position: [f32; 3] = "inPosition",
+    }
+}
+
+const fn square(position: [f32; 3], tex_coords: [f32; 2]) -> SquareVertex {
+    SquareVertex { position, tex_coords }
+}
+
+const fn face(position: [f32; 3], normal: [f32; 3], tex_coords: [f32; 2]) -> PlatformVertex {
+    PlatformVertex { position, normal, tex_coords }
+}
+
+const fn edge(position: [f32; 3]) -> EdgeVertex {
+    EdgeVertex { position }
+}
 
 // square, normal to the z axis
-pub static SQUARE_FACES: &[[GLfloat; 5]] = &[
-    [-0.5, -0.5,  0.0,   0.0, 0.0],
-    [-0.5,  0.5,  0.0,   0.0, 1.0],
-    [ 0.5, -0.5,  0.0,   1.0, 0.0],
-
-    [-0.5,  0.5,  0.0,   0.0, 1.0],
-    [ 0.5, -0.5,  0.0,   1.0, 0.0],
-    [ 0.5,  0.5,  0.0,   1.0, 1.0],
+pub static SQUARE_VERTICES: &[SquareVertex] = &[
+    square([-0.5, -0.5,  0.0], [0.0, 0.0]),
+    square([-0.5,  0.5,  0.0], [0.0, 1.0]),
+    square([ 0.5, -0.5,  0.0], [1.0, 0.0]),
+    square([ 0.5,  0.5,  0.0], [1.0, 1.0]),
+];
+
+pub static SQUARE_INDICES: &[u16] = &[
+    0, 1, 2,
+    1, 2, 3,
+];
+
+// the six faces of a unit box, each with texture coordinates spanning the
+// unit square. The camera never sees the top and bottom, only the sun does
+pub static PLATFORM_VERTICES: &[PlatformVertex] = &[
+    face([-0.5,  0.5,  0.5], [-1.0,  0.0,  0.0], [0.0, 1.0]),
+    face([-0.5,  0.5, -0.5], [-1.0,  0.0,  0.0], [0.0, 0.0]),
+    face([-0.5, -0.5, -0.5], [-1.0,  0.0,  0.0], [1.0, 0.0]),
+    face([-0.5, -0.5,  0.5], [-1.0,  0.0,  0.0], [1.0, 1.0]),
+
+    face([ 0.5,  0.5,  0.5], [ 1.0,  0.0,  0.0], [1.0, 1.0]),
+    face([ 0.5,  0.5, -0.5], [ 1.0,  0.0,  0.0], [1.0, 0.0]),
+    face([ 0.5, -0.5, -0.5], [ 1.0,  0.0,  0.0], [0.0, 0.0]),
+    face([ 0.5, -0.5,  0.5], [ 1.0,  0.0,  0.0], [0.0, 1.0]),
+
+    face([-0.5, -0.5, -0.5], [ 0.0, -1.0,  0.0], [0.0, 0.0]),
+    face([ 0.5, -0.5, -0.5], [ 0.0, -1.0,  0.0], [1.0, 0.0]),
+    face([ 0.5, -0.5,  0.5], [ 0.0, -1.0,  0.0], [1.0, 1.0]),
+    face([-0.5, -0.5,  0.5], [ 0.0, -1.0,  0.0], [0.0, 1.0]),
+
+    face([-0.5,  0.5, -0.5], [ 0.0,  1.0,  0.0], [1.0, 0.0]),
+    face([ 0.5,  0.5, -0.5], [ 0.0,  1.0,  0.0], [0.0, 0.0]),
+    face([ 0.5,  0.5,  0.5], [ 0.0,  1.0,  0.0], [0.0, 1.0]),
+    face([-0.5,  0.5,  0.5], [ 0.0,  1.0,  0.0], [1.0, 1.0]),
+
+    face([-0.5, -0.5,  0.5], [ 0.0,  0.0,  1.0], [0.0, 0.0]),
+    face([ 0.5, -0.5,  0.5], [ 0.0,  0.0,  1.0], [1.0, 0.0]),
+    face([ 0.5,  0.5,  0.5], [ 0.0,  0.0,  1.0], [1.0, 1.0]),
+    face([-0.5,  0.5,  0.5], [ 0.0,  0.0,  1.0], [0.0, 1.0]),
+
+    face([-0.5, -0.5, -0.5], [ 0.0,  0.0, -1.0], [0.0, 1.0]),
+    face([ 0.5, -0.5, -0.5], [ 0.0,  0.0, -1.0], [1.0, 1.0]),
+    face([ 0.5,  0.5, -0.5], [ 0.0,  0.0, -1.0], [1.0, 0.0]),
+    face([-0.5,  0.5, -0.5], [ 0.0,  0.0, -1.0], [0.0, 0.0]),
+];
+
+// two triangles per side
+pub static PLATFORM_FACE_INDICES: &[u16] = &[
+     0,  1,  2,   2,  3,  0,
+     4,  5,  6,   6,  7,  4,
+     8,  9, 10,  10, 11,  8,
+    12, 13, 14,  14, 15, 12,
 ];
 
-// triangles
-pub static PLATFORM_FACES: &[[GLfloat; 6]] = &[
-    [-0.5,  0.5,  0.5,   -1.0,  0.0,  0.0],
-    [-0.5,  0.5, -0.5,   -1.0,  0.0,  0.0],
-    [-0.5, -0.5, -0.5,   -1.0,  0.0,  0.0],
-    [-0.5, -0.5, -0.5,   -1.0,  0.0,  0.0],
-    [-0.5, -0.5,  0.5,   -1.0,  0.0,  0.0],
-    [-0.5,  0.5,  0.5,   -1.0,  0.0,  0.0],
-
-    [ 0.5,  0.5,  0.5,    1.0,  0.0,  0.0],
-    [ 0.5,  0.5, -0.5,    1.0,  0.0,  0.0],
-    [ 0.5, -0.5, -0.5,    1.0,  0.0,  0.0],
-    [ 0.5, -0.5, -0.5,    1.0,  0.0,  0.0],
-    [ 0.5, -0.5,  0.5,    1.0,  0.0,  0.0],
-    [ 0.5,  0.5,  0.5,    1.0,  0.0,  0.0],
-
-    [-0.5, -0.5, -0.5,    0.0, -1.0,  0.0],
-    [ 0.5, -0.5, -0.5,    0.0, -1.0,  0.0],
-    [ 0.5, -0.5,  0.5,    0.0, -1.0,  0.0],
-    [ 0.5, -0.5,  0.5,    0.0, -1.0,  0.0],
-    [-0.5, -0.5,  0.5,    0.0, -1.0,  0.0],
-    [-0.5, -0.5, -0.5,    0.0, -1.0,  0.0],
-
-    [-0.5,  0.5, -0.5,    0.0,  1.0,  0.0],
-    [ 0.5,  0.5, -0.5,    0.0,  1.0,  0.0],
-    [ 0.5,  0.5,  0.5,    0.0,  1.0,  0.0],
-    [ 0.5,  0.5,  0.5,    0.0,  1.0,  0.0],
-    [-0.5,  0.5,  0.5,    0.0,  1.0,  0.0],
-    [-0.5,  0.5, -0.5,    0.0,  1.0,  0.0],
+// every face, for casting shadows
+pub static PLATFORM_SOLID_INDICES: &[u16] = &[
+     0,  1,  2,   2,  3,  0,
+     4,  5,  6,   6,  7,  4,
+     8,  9, 10,  10, 11,  8,
+    12, 13, 14,  14, 15, 12,
+    16, 17, 18,  18, 19, 16,
+    20, 21, 22,  22, 23, 20,
 ];
 
-pub static PLATFORM_EDGES: &[[GLfloat; 3]] = &[
-    [-0.5, -0.5, -0.5],
-    [-0.5, -0.5,  0.5],
-
-    [ 0.5, -0.5, -0.5],
-    [ 0.5, -0.5,  0.5],
-
-    [-0.5,  0.5, -0.5],
-    [-0.5,  0.5,  0.5],
-
-    [ 0.5,  0.5, -0.5],
-    [ 0.5,  0.5,  0.5],
-
-    [-0.5, -0.5, -0.5],
-    [-0.5,  0.5, -0.5],
-               
-    [ 0.5, -0.5, -0.5],
-    [ 0.5,  0.5, -0.5],
-               
-    [-0.5, -0.5,  0.5],
-    [-0.5,  0.5,  0.5],
-               
-    [ 0.5, -0.5,  0.5],
-    [ 0.5,  0.5,  0.5],
-
-    [-0.5, -0.5, -0.5],
-    [ 0.5, -0.5, -0.5],
-                 
-    [-0.5,  0.5, -0.5],
-    [ 0.5,  0.5, -0.5],
-                 
-    [-0.5, -0.5,  0.5],
-    [ 0.5, -0.5,  0.5],
-                 
-    [-0.5,  0.5,  0.5],
-    [ 0.5,  0.5,  0.5],
+// the corners of the box
+pub static PLATFORM_CORNERS: &[EdgeVertex] = &[
+    edge([-0.5, -0.5, -0.5]),
+    edge([ 0.5, -0.5, -0.5]),
+    edge([-0.5,  0.5, -0.5]),
+    edge([ 0.5,  0.5, -0.5]),
+    edge([-0.5, -0.5,  0.5]),
+    edge([ 0.5, -0.5,  0.5]),
+    edge([-0.5,  0.5,  0.5]),
+    edge([ 0.5,  0.5,  0.5]),
+];
 
+// lines between corners, along z, y and x
+pub static PLATFORM_EDGE_INDICES: &[u16] = &[
+    0, 4,  1, 5,  2, 6,  3, 7,
+    0, 2,  1, 3,  4, 6,  5, 7,
+    0, 1,  2, 3,  4, 5,  6, 7,
 ];
@@ -0,0 +1,91 @@
+//! A tiny ini-style settings file:
+//!
+//! ```text
+//! # comment
+//! [section]
+//! key = value
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the file loaded by the game at startup, and again with `F5`.
+pub const SETTINGS_FILE: &str = "settings.ini";
+
+/// Where the settings are looked for without `--settings`: the working
+/// directory, or else next to the executable.
+pub fn default_path() -> PathBuf {
+    let in_working_dir = PathBuf::from(SETTINGS_FILE);
+    if in_working_dir.exists() {
+        return in_working_dir;
+    }
+
+    std::env::current_exe().ok()
+        .and_then(|exe| Some(exe.parent()?.join(SETTINGS_FILE)))
+        .filter(|path| path.exists())
+        .unwrap_or(in_working_dir)
+}
+
+#[derive(Default)]
+pub struct Settings {
+    sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl Settings {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Settings> {
+        let path = path.as_ref();
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn parse(text: &str) -> Result<Settings, String> {
+        let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut section = String::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_string();
+            } else if let Some((key, value)) = line.split_once('=') {
+                sections.entry(section.clone())
+                    .or_default()
+                    .insert(key.trim().to_string(), value.trim().to_string());
+            } else {
+                return Err(format!("line {}: expected `[section]` or `key = value`", i + 1));
+            }
+        }
+
+        Ok(Settings {
+            sections,
+        })
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section)?.get(key).map(String::as_str)
+    }
+
+    /// Like `get`, but warns about and ignores values that don't parse.
+    pub fn get_parsed<T: std::str::FromStr>(&self, section: &str, key: &str) -> Option<T> {
+        let value = self.get(section, key)?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            log::warn!("settings: ignoring invalid value {:?} for {}.{}", value, section, key);
+        }
+
+        parsed
+    }
+
+    /// Every key and value in `section`, in no particular order.
+    pub fn section(&self, section: &str) -> impl Iterator<Item = (&str, &str)> {
+        self.sections.get(section)
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
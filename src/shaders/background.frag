@@ -0,0 +1,63 @@
+#version 150 core
+
+// the sky and the silhouettes of far-off scenery, behind everything else
+
+// must match MAX_LAYERS in resources/background.rs
+#define MAX_LAYERS 4
+
+const float TAU = 6.28318531;
+
+// 0 to 1 across the screen, y up
+in vec2 TexCoords;
+
+out vec4 outColor;
+
+uniform vec3 sky_top;
+uniform vec3 sky_bottom;
+// how far the camera has turned around the level, in whole turns
+uniform float turns;
+
+uniform int layer_count;
+// rgb, and 1 for a skyline or 0 for hills
+uniform vec4 layer_colors[MAX_LAYERS];
+// height, amplitude, bumps and parallax
+uniform vec4 layer_shapes[MAX_LAYERS];
+uniform float layer_seeds[MAX_LAYERS];
+
+float hash(float n) {
+    return fract(sin(n) * 43758.5453);
+}
+
+// height of the layer's top edge at `s`, from 0 to 1 around the level
+float edge(int i, float s) {
+    vec4 shape = layer_shapes[i];
+    float bumps = shape.z;
+    float seed = layer_seeds[i];
+
+    float offset;
+    if (layer_colors[i].a > 0.5) {
+        offset = 2.0 * hash(floor(s * bumps) + 17.0*seed) - 1.0;
+    } else {
+        // whole numbers of waves, so they meet up all the way around
+        offset = 0.7 * sin(TAU * bumps * s + 10.0*seed)
+               + 0.3 * sin(TAU * (2.0*bumps + 1.0) * s + 23.0*seed);
+    }
+
+    return shape.x + shape.y * offset;
+}
+
+void main() {
+    vec3 color = mix(sky_bottom, sky_top, TexCoords.y);
+
+    for (int i = 0; i < layer_count; i++) {
+        // a quarter turn scrolls the layer by `parallax` screens
+        float parallax = max(layer_shapes[i].w, 0.001);
+        float s = fract(turns + (TexCoords.x - 0.5) / (4.0 * parallax));
+
+        if (TexCoords.y < edge(i, s)) {
+            color = layer_colors[i].rgb;
+        }
+    }
+
+    outColor = vec4(color, 1.0);
+}
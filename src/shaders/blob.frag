@@ -0,0 +1,18 @@
+#version 150 core
+
+in vec2 TexCoords;
+
+out vec4 outColor;
+
+// how dark the middle of the blob is
+uniform float opacity;
+
+void main() {
+    // only the half below the platform's top edge
+    if (TexCoords.y > 0.5) {
+        discard;
+    }
+
+    float blob = 1.0 - smoothstep(0.6, 1.0, length(TexCoords*2.0 - 1.0));
+    outColor = vec4(0.0, 0.0, 0.0, opacity * blob);
+}
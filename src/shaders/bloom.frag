@@ -0,0 +1,33 @@
+#version 150
+
+in vec2 TexCoords;
+out vec4 outColor;
+
+uniform sampler2D scene;
+uniform vec2 resolution;
+// brightness above which pixels start to glow
+uniform float threshold;
+uniform float intensity;
+// reach of the glow, in pixels
+uniform float radius;
+
+vec3 bright(vec2 uv) {
+    vec3 color = texture(scene, uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    return color * smoothstep(threshold, threshold + 0.1, brightness);
+}
+
+void main() {
+    // a 9x9 gaussian over the bright parts only
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int x = -4; x <= 4; x++) {
+        for (int y = -4; y <= 4; y++) {
+            float weight = exp(-float(x*x + y*y) / 8.0);
+            glow += weight * bright(TexCoords + vec2(x, y) * radius / (4.0 * resolution));
+            total += weight;
+        }
+    }
+
+    outColor = vec4(texture(scene, TexCoords).rgb + intensity * glow / total, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 150
+
+in vec2 TexCoords;
+out vec4 outColor;
+
+uniform sampler2D scene;
+uniform vec2 resolution;
+uniform float exposure;
+uniform float contrast;
+uniform float saturation;
+// multiplied into the result
+uniform float tint_r;
+uniform float tint_g;
+uniform float tint_b;
+
+void main() {
+    vec3 color = texture(scene, TexCoords).rgb * exposure;
+    color = (color - 0.5) * contrast + 0.5;
+
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luma), color, saturation);
+
+    outColor = vec4(clamp(color * vec3(tint_r, tint_g, tint_b), 0.0, 1.0), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 150 core
+
+// post-processing: a single triangle covering the screen, generated from
+// the vertex index so no vertex buffer is needed
+
+out vec2 TexCoords;
+
+void main() {
+    // (0, 0), (2, 0), (0, 2)
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    TexCoords = pos;
+    gl_Position = vec4(2.0*pos - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,66 @@
+// the level's lights, in world space. See resources::lighting
+
+#define MAX_LANTERNS 8
+
+uniform vec3 ambient;
+// the direction sunlight travels in
+uniform vec3 sun_direction;
+uniform vec3 sun_color;
+uniform int lantern_count;
+// position and radius
+uniform vec4 lanterns[MAX_LANTERNS];
+uniform vec4 lantern_colors[MAX_LANTERNS];
+// towards the camera
+uniform vec3 eye_direction;
+// depth of the scene as seen from the sun
+uniform sampler2DShadow shadow_map;
+// from world space to the shadow map's clip space
+uniform mat4 light_matrix;
+uniform vec3 fog_color;
+// depths where fog starts and where it's thickest
+uniform vec2 fog_range;
+// how much of the fog color shows at the far end of the range
+uniform float fog_density;
+
+// how much sunlight reaches `position`, from 0 to 1
+float sunlight(vec3 position, vec3 normal) {
+    // looking up a bit off the surface keeps it from shadowing itself
+    vec3 coords = (light_matrix * vec4(position + 0.05*normal, 1.0)).xyz * 0.5 + 0.5;
+    return texture(shadow_map, coords);
+}
+
+// diffuse and blinn-phong specular light of `light_color`, coming from
+// `to_light`
+vec3 light(vec3 color, vec3 normal, vec3 to_light, vec3 light_color, float shininess) {
+    float diffuse = max(dot(normal, to_light), 0.0);
+    vec3 result = diffuse * light_color * color;
+    if (shininess > 0.0 && diffuse > 0.0) {
+        vec3 halfway = normalize(to_light + eye_direction);
+        result += pow(max(dot(normal, halfway), 0.0), shininess) * light_color;
+    }
+
+    return result;
+}
+
+// `color` at `position` lit by every light. A shininess of zero turns
+// specular highlights off
+vec3 shade(vec3 color, vec3 position, vec3 normal, float shininess) {
+    normal = normalize(normal);
+
+    vec3 result = ambient * color + sunlight(position, normal) * light(color, normal, -sun_direction, sun_color, shininess);
+    for (int i = 0; i < lantern_count; i++) {
+        vec3 offset = lanterns[i].xyz - position;
+        float falloff = clamp(1.0 - length(offset) / lanterns[i].w, 0.0, 1.0);
+        result += light(color, normal, normalize(offset), falloff*falloff * lantern_colors[i].rgb, shininess);
+    }
+
+    return result;
+}
+
+// `color` at `position` faded towards the fog color, by its depth along
+// the view axis: away from the camera, from the plane through the origin
+vec3 fog(vec3 color, vec3 position) {
+    float depth = -dot(position, eye_direction);
+    float amount = clamp((depth - fog_range.x) / max(fog_range.y - fog_range.x, 0.0001), 0.0, 1.0);
+    return mix(color, fog_color, fog_density * amount);
+}
@@ -0,0 +1,14 @@
+#version 150
+
+in vec3 inPosition;
+in vec3 inColor;
+
+out vec3 Color;
+
+uniform mat4 view;
+uniform mat4 proj;
+
+void main() {
+    Color = inColor;
+    gl_Position = proj * view * vec4(inPosition, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 150
+
+in vec2 TexCoords;
+in vec4 Color;
+
+out vec4 outColor;
+
+void main() {
+    // a round speck, soft at the edge
+    float speck = 1.0 - smoothstep(0.6, 1.0, length(TexCoords*2.0 - 1.0));
+    outColor = vec4(Color.rgb, Color.a * speck);
+}
@@ -0,0 +1,25 @@
+#version 150
+
+// the square mesh, one instance per particle
+
+in vec3 inPosition;
+in vec2 inTexCoords;
+in vec3 inCenter;
+in float inSize;
+in vec4 inColor;
+
+out vec2 TexCoords;
+out vec4 Color;
+
+uniform mat4 view;
+uniform mat4 proj;
+// turns the square to face the camera
+uniform mat4 billboard;
+
+void main() {
+    TexCoords = inTexCoords;
+    Color = inColor;
+
+    vec3 position = inCenter + inSize * mat3(billboard) * inPosition;
+    gl_Position = proj * view * vec4(position, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 150
+
+// every pass samples the previous result through `scene`, and knows the
+// size of the target in pixels
+
+in vec2 TexCoords;
+out vec4 outColor;
+
+uniform sampler2D scene;
+uniform vec2 resolution;
+// side of a block, in pixels
+uniform float size;
+
+void main() {
+    vec2 block = max(size, 1.0) / resolution;
+    outColor = texture(scene, (floor(TexCoords / block) + 0.5) * block);
+}
@@ -0,0 +1,16 @@
+#version 150
+
+in vec2 TexCoords;
+out vec4 outColor;
+
+uniform sampler2D scene;
+uniform vec2 resolution;
+// how dark the gaps between lines get
+uniform float intensity;
+// height of a line, in pixels
+uniform float spacing;
+
+void main() {
+    float line = 0.5 + 0.5*cos(6.2831853 * TexCoords.y * resolution.y / max(spacing, 1.0));
+    outColor = vec4(texture(scene, TexCoords).rgb * (1.0 - intensity*line), 1.0);
+}
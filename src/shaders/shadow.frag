@@ -0,0 +1,6 @@
+#version 150 core
+
+// only depth is written, from the sun's point of view
+
+void main() {
+}
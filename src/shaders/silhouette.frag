@@ -0,0 +1,23 @@
+#version 150
+
+// a sprite, flat colored, for where it is hidden behind something
+
+in vec2 TexCoords;
+// offset and size of the texture within the atlas page
+flat in vec4 UvRect;
+
+out vec4 outColor;
+
+uniform sampler2D tex;
+uniform vec3 tint;
+uniform float opacity;
+
+void main() {
+    // the same cutout as solid.frag
+    vec2 uv = fract(vec2(TexCoords.x, -TexCoords.y));
+    if (texture(tex, UvRect.xy + uv*UvRect.zw).a < 0.5) {
+        discard;
+    }
+
+    outColor = vec4(tint, opacity);
+}
@@ -0,0 +1,33 @@
+#version 150
+
+in vec3 Position;
+in vec3 Normal;
+in vec3 Color;
+in vec2 TexCoords;
+// offset and size of the texture within the atlas page
+flat in vec4 UvRect;
+flat in float Shininess;
+
+out vec4 outColor;
+
+uniform bool apply_diffuse;
+uniform bool textured;
+uniform sampler2D tex;
+
+#include "lighting.glsl"
+
+void main() {
+    vec4 base = vec4(Color, 1.0);
+    if (textured) {
+        // png rows go top to bottom; wrap by hand since the texture is
+        // only a part of the page
+        vec2 uv = fract(vec2(TexCoords.x, -TexCoords.y));
+        base *= texture(tex, UvRect.xy + uv*UvRect.zw);
+        if (base.a < 0.5) {
+            discard;
+        }
+    }
+
+    vec3 color = apply_diffuse ? shade(base.rgb, Position, Normal, Shininess) : base.rgb;
+    outColor = vec4(fog(color, Position), 1.0);
+}
@@ -0,0 +1,62 @@
+#version 150 core
+
+// with INSTANCED defined, the model matrix, color, atlas rect and
+// shininess come from per-instance attributes, as used for platforms
+
+in vec3 inPosition;
+in vec3 inNormal;
+in vec2 inTexCoords;
+#ifdef INSTANCED
+in mat4 inModel;
+in vec3 inColor;
+in vec4 inUvRect;
+in float inShininess;
+#endif
+
+// in world space, for lighting
+out vec3 Position;
+out vec3 Normal;
+out vec3 Color;
+out vec2 TexCoords;
+flat out vec4 UvRect;
+flat out float Shininess;
+
+uniform mat4 view;
+uniform mat4 proj;
+uniform vec3 color;
+#ifdef INSTANCED
+uniform bool use_instance_color;
+#else
+uniform mat4 model;
+uniform vec4 uv_rect;
+uniform float shininess;
+#endif
+
+void main() {
+#ifdef INSTANCED
+    mat4 model = inModel;
+    Color = use_instance_color ? inColor : color;
+    UvRect = inUvRect;
+    Shininess = inShininess;
+
+    // tile the texture once per unit, using the platform's dimensions
+    // along the face's axes
+    vec3 dim = vec3(model[0][0], model[1][1], model[2][2]);
+    if (inNormal.x != 0.0) {
+        TexCoords = inTexCoords * dim.yz;
+    } else if (inNormal.y != 0.0) {
+        TexCoords = inTexCoords * dim.xz;
+    } else {
+        TexCoords = inTexCoords * dim.xy;
+    }
+#else
+    Color = color;
+    UvRect = uv_rect;
+    Shininess = shininess;
+    TexCoords = inTexCoords;
+#endif
+
+    Position = vec3(model * vec4(inPosition, 1.0));
+    gl_Position = proj * view * vec4(Position, 1.0);
+    Normal = inNormal * inverse(mat3(model));
+}
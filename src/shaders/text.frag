@@ -0,0 +1,13 @@
+#version 150
+
+in vec2 TexCoords;
+in vec4 Color;
+
+out vec4 outColor;
+
+// the font page, white glyphs on transparent
+uniform sampler2D font;
+
+void main() {
+    outColor = Color * texture(font, TexCoords);
+}
@@ -0,0 +1,19 @@
+#version 150
+
+// screen space, in pixels from the top left corner
+in vec2 inPosition;
+in vec2 inTexCoords;
+in vec4 inColor;
+
+out vec2 TexCoords;
+out vec4 Color;
+
+uniform vec2 screen_size;
+
+void main() {
+    TexCoords = inTexCoords;
+    Color = inColor;
+
+    vec2 position = inPosition / screen_size * 2.0 - 1.0;
+    gl_Position = vec4(position.x, -position.y, 0.0, 1.0);
+}
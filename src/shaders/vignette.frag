@@ -0,0 +1,17 @@
+#version 150
+
+in vec2 TexCoords;
+out vec4 outColor;
+
+uniform sampler2D scene;
+uniform vec2 resolution;
+uniform float strength;
+// distance from the center, in half screen heights, where darkening starts
+uniform float radius;
+uniform float softness;
+
+void main() {
+    vec2 offset = (TexCoords - 0.5) * vec2(resolution.x / resolution.y, 1.0) * 2.0;
+    float shade = smoothstep(radius, radius + softness, length(offset));
+    outColor = vec4(texture(scene, TexCoords).rgb * (1.0 - strength*shade), 1.0);
+}
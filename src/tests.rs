@@ -0,0 +1,543 @@
+//! Golden-image tests. These render fixed scenes on Mesa's software
+//! rasterizer through a surfaceless EGL context, so no GPU or window system
+//! is needed, and compare the result against the pngs in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to overwrite the golden images. Mismatching
+//! renders are written to `target/golden` for inspection.
+//!
+//! The text rendering, buffer, render target, shader reloading and
+//! preprocessing tests use the same context, and the png decoding and text
+//! layout tests need none.
+
+use super::*;
+use khronos_egl as egl;
+use resources::Image;
+use resources::font::Align;
+use std::path::PathBuf;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+// a pixel counts as different if any channel is off by more than this...
+const CHANNEL_TOLERANCE: u8 = 16;
+// ...and an image if more than this fraction of its pixels differ
+const PIXEL_TOLERANCE: f32 = 0.002;
+
+type HeadlessContext = (egl::DynamicInstance<egl::EGL1_5>, egl::Display, egl::Context);
+
+fn headless_context() -> Result<HeadlessContext, String> {
+    // always use llvmpipe, so the output doesn't depend on the host's gpu
+    std::env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+    std::env::set_var("GALLIUM_DRIVER", "llvmpipe");
+
+    let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+        .map_err(|err| format!("could not load libEGL: {}", err))?;
+
+    const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+    let display = egl.get_platform_display(PLATFORM_SURFACELESS_MESA, std::ptr::null_mut(), &[egl::ATTRIB_NONE])
+        .map_err(|err| format!("could not get a surfaceless display: {}", err))?;
+    egl.initialize(display).map_err(|err| err.to_string())?;
+    egl.bind_api(egl::OPENGL_API).map_err(|err| err.to_string())?;
+
+    let config = egl.choose_first_config(display, &[
+        egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+        egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+        egl::NONE,
+    ]).map_err(|err| err.to_string())?.ok_or("no suitable egl config")?;
+
+    let context = egl.create_context(display, config, None, &[
+        egl::CONTEXT_MAJOR_VERSION, 3,
+        egl::CONTEXT_MINOR_VERSION, 3,
+        egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+        egl::CONTEXT_OPENGL_DEBUG, egl::TRUE as egl::Int,
+        egl::NONE,
+    ]).map_err(|err| err.to_string())?;
+    egl.make_current(display, None, None, Some(context)).map_err(|err| err.to_string())?;
+
+    gl::load_with(|s| egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _));
+
+    // driver messages show up in the output of failing tests, where the
+    // driver can report them
+    let _ = env_logger::builder().is_test(true).try_init();
+    if !gfx::debug::enable() {
+        log::warn!("KHR_debug is not supported, no driver messages will be logged");
+    }
+
+    Ok((egl, display, context))
+}
+
+/// Fraction of pixels that differ by more than the channel tolerance.
+fn difference(a: &Image, b: &Image) -> f32 {
+    if (a.width, a.height) != (b.width, b.height) {
+        return 1.0;
+    }
+
+    let differing = a.pixels.chunks_exact(4).zip(b.pixels.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| (*a as i16 - *b as i16).abs() > CHANNEL_TOLERANCE as i16))
+        .count();
+
+    differing as f32 / (a.width*a.height) as f32
+}
+
+/// Compares `image` against the golden image `name`, or overwrites it with
+/// `UPDATE_GOLDEN` set. Describes the mismatch, if any.
+fn compare_golden(image: &Image, name: &str) -> Result<(), String> {
+    let golden_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let failure_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+    let path = golden_dir.join(name).with_extension("png");
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(&golden_dir).unwrap();
+        image.save_png(&path).unwrap();
+        return Ok(());
+    }
+
+    let golden = Image::load_png(&path)
+        .unwrap_or_else(|err| panic!("could not load {}: {}", path.display(), err));
+    let difference = difference(image, &golden);
+    if difference > PIXEL_TOLERANCE {
+        std::fs::create_dir_all(&failure_dir).unwrap();
+        image.save_png(failure_dir.join(name).with_extension("png")).unwrap();
+        return Err(format!("{}: {:.2}% of pixels differ", name, 100.0*difference));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn golden_images() {
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let mut game = Game::new(ShaderSources::embedded(), Level::demo(), STARTING_POSITION).unwrap_or_else(|err| panic!("{}", err));
+    let mut failures = Vec::new();
+    let mut check = |game: &Game, name: &str| {
+        let image = game.res.render_offscreen(WIDTH, HEIGHT, |_| game.draw()).unwrap();
+        if let Err(failure) = compare_golden(&image, name) {
+            failures.push(failure);
+        }
+    };
+
+    // let the player land and settle
+    for _ in 0..60 {
+        game.step(1.0/60.0);
+    }
+    check(&game, "s");
+
+    // quarter turns to the left, stopping halfway through the first one
+    game.move_camera_left();
+    game.step(0.25);
+    check(&game, "s_to_w_halfway");
+    game.step(0.25);
+    check(&game, "w");
+
+    game.move_camera_left();
+    game.step(0.5);
+    check(&game, "n");
+
+    game.move_camera_left();
+    game.step(0.5);
+    check(&game, "e");
+
+    game.move_camera_left();
+    game.step(0.125);
+    check(&game, "e_to_s_quarter");
+    game.step(0.375);
+
+    // and one to the right
+    game.move_camera_right();
+    game.step(0.375);
+    check(&game, "s_to_e_three_quarters");
+    game.step(0.125);
+
+    // every post-processing pass, with its default params
+    for pass in game.post.passes_mut() {
+        pass.set_enabled(true);
+    }
+    check(&game, "e_postprocessed");
+
+    // collision shapes and state, a little into a jump
+    for pass in game.post.passes_mut() {
+        pass.set_enabled(false);
+    }
+    game.show_debug_overlay = true;
+    game.player.press_jump();
+    game.step(0.1);
+    check(&game, "e_debug_overlay");
+
+    // the sparkle of respawning, halfway through
+    game.show_debug_overlay = false;
+    game.respawn();
+    game.step(0.3);
+    check(&game, "e_respawned");
+
+    // walked around behind the tower, which shows their silhouette. last,
+    // since it moves the player
+    game.player.pos = (-3.0, 0.0, -6.0);
+    game.player.behind_wall = true;
+    game.step(1.0/60.0);
+    check(&game, "e_behind_tower");
+
+    assert!(failures.is_empty(), "golden images differ (see target/golden):\n{}", failures.join("\n"));
+}
+
+#[test]
+fn per_platform_draws() {
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let mut game = Game::new(ShaderSources::embedded(), Level::demo(), STARTING_POSITION).unwrap_or_else(|err| panic!("{}", err));
+    game.step(1.0/60.0);
+    let draw = |game: &Game| game.res.render_offscreen(WIDTH, HEIGHT, |_| game.draw()).unwrap();
+
+    // the same picture, just with more draw calls
+    let instanced = draw(&game);
+    game.per_platform_draws = true;
+    assert_eq!(difference(&draw(&game), &instanced), 0.0);
+}
+
+#[test]
+fn collecting_sparkles() {
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let mut game = Game::new(ShaderSources::embedded(), Level::demo(), STARTING_POSITION).unwrap_or_else(|err| panic!("{}", err));
+    game.step(1.0/60.0);
+    let before = game.particles.particles().len();
+
+    game.player.collect();
+    game.step(0.0);
+    assert_eq!(game.particles.particles().len(), before + particles::SPARKLE_COUNT as usize);
+}
+
+#[test]
+fn buffer_sub_ranges() {
+    use gfx::{Buffer, BufferTarget, BufferUsage};
+
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    fn read_back(buffer: &Buffer<u32>) -> Vec<u32> {
+        let mut data = vec![0u32; buffer.len()];
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, buffer.name());
+            gl::GetBufferSubData(gl::COPY_READ_BUFFER, 0, (data.len()*4) as isize, data.as_mut_ptr() as *mut _);
+        }
+        data
+    }
+
+    let mut buffer = Buffer::from_data(BufferTarget::Array, BufferUsage::Dynamic, &[0u32, 1, 2, 3, 4, 5]);
+    buffer.update(1, &[10, 20]);
+    assert_eq!(read_back(&buffer), [0, 10, 20, 3, 4, 5]);
+
+    {
+        let mut mapped = buffer.map(2..5);
+        assert_eq!(&mapped[..], [20, 3, 4]);
+        mapped[1] = 30;
+        mapped[2] = 40;
+    }
+    assert_eq!(read_back(&buffer), [0, 10, 20, 30, 40, 5]);
+}
+
+#[test]
+fn render_targets() {
+    use gfx::{DepthTarget, RenderTarget};
+
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let draw_binding = || {
+        let mut binding = 0;
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut binding);
+        }
+        binding
+    };
+
+    // creating targets doesn't take over whatever is bound
+    let target = RenderTarget::new(4, 4, 4, false).unwrap();
+    target.bind();
+    let bound = draw_binding();
+    let _other = RenderTarget::new(4, 4, 4, true).unwrap();
+    let _shadow = DepthTarget::new(4, 4).unwrap();
+    assert_eq!(draw_binding(), bound);
+
+    // multisampled color shows up once resolved, which keeps the bindings
+    unsafe {
+        gl::ClearColor(0.0, 1.0, 0.0, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+    target.resolve();
+    assert_eq!(draw_binding(), bound);
+    target.bind_read();
+    assert_eq!(Image::read_framebuffer(4, 4).pixel(1, 1), [0, 255, 0, 255]);
+}
+
+#[test]
+fn text_layout() {
+    use resources::font::{Align, Font, Layout};
+
+    // three pixel wide glyphs advancing by four, and a kerning pair
+    let font = Font::parse("info face=\"test\" size=5
+common lineHeight=6 base=5 scaleW=64 scaleH=8 pages=1
+page id=0 file=\"test.png\"
+chars count=4
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0
+char id=63 x=4 y=0 width=3 height=5 xoffset=0 yoffset=0 xadvance=4 page=0
+char id=97 x=8 y=0 width=3 height=5 xoffset=0 yoffset=1 xadvance=4 page=0
+char id=98 x=12 y=0 width=3 height=5 xoffset=1 yoffset=0 xadvance=4 page=0
+kerning first=97 second=98 amount=-1
+").unwrap();
+
+    assert_eq!(font.page, PathBuf::from("test.png"));
+    assert_eq!(font.width("ab"), 7.0);
+    // é isn't in the font, so it's as wide as the replacement
+    assert_eq!(font.width("aé"), 8.0);
+
+    assert_eq!(font.wrap("aa bb\nab", None), ["aa bb", "ab"]);
+    assert_eq!(font.wrap("aa bb a", Some(16.0)), ["aa", "bb a"]);
+    assert_eq!(font.wrap("aaaaa", Some(8.0)), ["aa", "aa", "a"]);
+    assert_eq!(font.wrap("a\n\nb", Some(8.0)), ["a", "", "b"]);
+
+    let positions = |text: &str, align| font.layout(text, &Layout { align, max_width: None })
+        .iter()
+        .map(|placed| placed.position)
+        .collect::<Vec<_>>();
+    // offsets, kerning and the space, which has nothing to draw
+    assert_eq!(positions("ab a", Align::Left), [(0.0, 1.0), (4.0, 0.0), (11.0, 1.0)]);
+    assert_eq!(positions("ab", Align::Right), [(-7.0, 1.0), (-3.0, 0.0)]);
+    assert_eq!(positions("a\naaa", Align::Center), [(-2.0, 1.0), (-6.0, 7.0), (-2.0, 7.0), (2.0, 7.0)]);
+
+    assert!(Font::parse("common lineHeight=6 base=5 scaleW=64 scaleH=8 pages=2").is_err());
+    assert!(Font::parse("char id=97 x=0").is_err());
+    let page = "common lineHeight=6 base=5 scaleW=64 scaleH=8 pages=1\npage id=0 file=\"test.png\"\n";
+    // negative, or past the edge of the page
+    assert!(Font::parse(&format!("{}char id=97 x=-1 y=0 width=3 height=5 xoffset=0 yoffset=0 xadvance=4", page)).is_err());
+    assert!(Font::parse(&format!("{}char id=97 x=62 y=0 width=3 height=5 xoffset=0 yoffset=0 xadvance=4", page)).is_err());
+    assert!(Font::parse(&format!("{}char id=97 x=4294967295 y=0 width=3 height=5 xoffset=0 yoffset=0 xadvance=4", page)).is_err());
+}
+
+#[test]
+fn text_rendering() {
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let res = Resources::new(&ShaderSources::embedded()).unwrap_or_else(|err| panic!("{}", err));
+    let image = res.render_offscreen(WIDTH, HEIGHT, |res| {
+        res.clear((0.1, 0.1, 0.1));
+
+        let text = res.text();
+        text.queue("Hello, world! 0123456789", (8.0, 8.0), &TextStyle::default());
+        text.queue("ÀBC?", (WIDTH as f32 - 8.0, HEIGHT as f32 - 16.0), &TextStyle {
+            scale: 1.0,
+            color: (1.0, 0.8, 0.3, 1.0),
+            align: Align::Right,
+            ..TextStyle::default()
+        });
+        text.queue("The quick brown fox jumps over the lazy dog {[(<>)]} 12° ×2 → …", (WIDTH as f32 / 2.0, 40.0), &TextStyle {
+            color: (0.5, 0.8, 1.0, 0.8),
+            align: Align::Center,
+            max_width: Some(200.0),
+            ..TextStyle::default()
+        });
+        text.flush();
+    }).unwrap();
+
+    compare_golden(&image, "text").unwrap();
+}
+
+// there's nothing to draw in release builds
+#[cfg(debug_assertions)]
+#[test]
+fn debug_drawing() {
+    use cgmath::Vector3;
+
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let mut game = Game::new(ShaderSources::embedded(), Level::demo(), STARTING_POSITION).unwrap_or_else(|err| panic!("{}", err));
+    game.step(1.0/60.0);
+    let draw = |game: &Game| game.res.render_offscreen(WIDTH, HEIGHT, |_| game.draw()).unwrap();
+
+    let line = debug_draw!(line(Vector3::new(-4.0, -3.0, -5.0), Vector3::new(4.0, 3.0, -5.0), (1.0, 1.0, 0.4)));
+    debug_draw!(aabb(Vector3::new(-1.0, -1.0, -3.0), Vector3::new(1.0, 1.0, -1.0), (1.0, 0.4, 0.4))).for_frames(3);
+    debug_draw!(sphere(Vector3::new(2.0, -2.0, -2.0), 0.75, (0.4, 1.0, 0.4))).for_seconds(60.0);
+    debug_draw!(arrow(Vector3::new(-3.0, -3.0, -4.0), Vector3::new(-3.0, -3.0, -2.0), (0.4, 0.6, 1.0)));
+    debug_draw!(text(Vector3::new(2.0, -2.0, -1.0), "sphere", (0.4, 1.0, 0.4)));
+    debug_draw!(screen_rect((8.0, 8.0), (120.0, 40.0), (1.0, 1.0, 1.0)));
+    debug_draw!(screen_line((8.0, 48.0), (120.0, 48.0), (1.0, 0.4, 1.0)));
+    debug_draw!(screen_text((16.0, 20.0), "screen", (1.0, 1.0, 1.0)));
+    assert_eq!(debug_draw::queued(), 8);
+
+    compare_golden(&draw(&game), "debug_draw").unwrap();
+    // single frame shapes are gone after one flush, the rest stay
+    assert_eq!(debug_draw::queued(), 2);
+    // and don't take over a shape left behind
+    line.for_frames(10);
+    draw(&game);
+    draw(&game);
+    assert_eq!(debug_draw::queued(), 1);
+}
+
+#[test]
+fn shader_reload() {
+    use cgmath::Vector3;
+    use gfx::*;
+
+    const VERTEX: &str = "#version 150 core
+void main() {
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(2.0*pos - 1.0, 0.0, 1.0);
+}
+";
+
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/shader_reload");
+    std::fs::create_dir_all(&dir).unwrap();
+    // explicit modification times, as writes in quick succession may share one
+    let mut edits = 0;
+    let mut write = |name: &str, source: &str| {
+        let file = std::fs::File::create(dir.join(name)).unwrap();
+        std::io::Write::write_all(&mut &file, source.as_bytes()).unwrap();
+        edits += 1;
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(edits)).unwrap();
+    };
+    write("reload.vert", VERTEX);
+    write("reload.frag", "#version 150
+out vec4 outColor;
+uniform vec3 color;
+void main() { outColor = vec4(color, 1.0); }
+");
+
+    let shaders = ShaderSources::from_dir(&dir);
+    let stages = &[(ShaderType::Vertex, "reload.vert"), (ShaderType::Fragment, "reload.frag")];
+    let program = shaders.build_program("reload", stages, &[]).unwrap();
+    let color = program.uniform::<Vector3<f32>>(c_str!("color")).unwrap();
+
+    let target = RenderTarget::new(4, 4, 1, false).unwrap();
+    let vao = VertexArrayObject::new();
+    let draw = || {
+        target.bind();
+        vao.bind();
+        program.bind().set(&color, Vector3::new(1.0, 0.0, 0.0));
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+        target.bind_read();
+        Image::read_framebuffer(4, 4).pixel(1, 1).to_vec()
+    };
+    assert_eq!(draw(), [255, 0, 0, 255]);
+
+    // a broken edit keeps the program as it was
+    write("reload.frag", "#version 150\nvoid main() { oops }\n");
+    let changed = shaders.changed();
+    assert_eq!(changed, ["reload.frag"]);
+    shaders.reload(&program, "reload", stages, &[], &changed);
+    assert_eq!(program.generation(), 0);
+    assert_eq!(draw(), [255, 0, 0, 255]);
+
+    // fixing it relinks, and `color` is set again even though its value
+    // didn't change
+    write("reload.frag", "#version 150
+out vec4 outColor;
+uniform float brightness;
+uniform vec3 color;
+void main() { outColor = vec4(color.bgr * brightness, 1.0); }
+");
+    shaders.reload(&program, "reload", stages, &[], &shaders.changed());
+    assert_eq!(program.generation(), 1);
+    let brightness = program.uniform::<f32>(c_str!("brightness")).unwrap();
+    program.bind().set(&brightness, 1.0);
+    assert_eq!(draw(), [0, 0, 255, 255]);
+
+    assert!(shaders.changed().is_empty());
+}
+
+#[test]
+fn shader_preprocessing() {
+    use gfx::*;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    let _context = headless_context().expect("a software opengl context (mesa's llvmpipe) is required");
+
+    let mut files = HashMap::new();
+    files.insert("main.frag", "#version 150
+#include \"a.glsl\"
+#include \"b.glsl\"
+out vec4 outColor;
+void main() { outColor = vec4(A + B, 1.0); }
+");
+    // b is included twice, which would redefine B without include guards
+    files.insert("a.glsl", "#include \"b.glsl\"\nconst vec3 A = COLOR;\n");
+    files.insert("b.glsl", "// comment\nconst vec3 B = vec3(0.0);\n");
+
+    let compile = |files: &HashMap<&str, &'static str>| {
+        let preprocessed = preprocess("main.frag", &[("COLOR", "vec3(1.0)")], |name| {
+            files.get(name)
+                .map(|source| Cow::Borrowed(*source))
+                .ok_or_else(|| Error::ReadSource { path: name.into(), error: std::io::ErrorKind::NotFound.into() })
+        })?;
+        assert_eq!(preprocessed.files, ["main.frag", "a.glsl", "b.glsl"]);
+
+        Shader::compile(ShaderType::Fragment, "main.frag", &preprocessed.source)
+            .map_err(|err| preprocessed.map_error(err))
+    };
+    compile(&files).unwrap_or_else(|err| panic!("{}", err));
+
+    // errors point at the file and line they're in
+    let log = |files: &HashMap<&str, &'static str>| match compile(files) {
+        Err(Error::Compile { log, .. }) => log,
+        Err(err) => panic!("{}", err),
+        Ok(_) => panic!("compiled a broken shader"),
+    };
+    files.insert("b.glsl", "// comment\nconst vec3 B = oops;\n");
+    let b_log = log(&files);
+    files.insert("b.glsl", "// comment\nconst vec3 B = vec3(0.0);\n");
+    files.insert("main.frag", "#version 150
+#include \"a.glsl\"
+#include \"b.glsl\"
+out vec4 outColor;
+void main() { outColor = vec4(A + B, 1.0) + oops; }
+");
+    let main_log = log(&files);
+
+    assert!(b_log.starts_with("b.glsl:2("), "{}", b_log);
+    assert!(main_log.starts_with("main.frag:5("), "{}", main_log);
+}
+
+#[test]
+fn png_formats() {
+    use png::{BitDepth, ColorType};
+
+    fn encode(color: ColorType, depth: BitDepth, palette: Option<(Vec<u8>, Vec<u8>)>, data: &[u8]) -> Vec<u8> {
+        let mut png = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png, 2, 1);
+            encoder.set_color(color);
+            encoder.set_depth(depth);
+            let mut writer = encoder.write_header().unwrap();
+            if let Some((palette, trns)) = palette {
+                writer.write_chunk(png::chunk::PLTE, &palette).unwrap();
+                writer.write_chunk(png::chunk::tRNS, &trns).unwrap();
+            }
+            writer.write_image_data(data).unwrap();
+        }
+        png
+    }
+
+    let cases = [
+        ("grayscale", encode(ColorType::Grayscale, BitDepth::Eight, None, &[0, 200]),
+            [0, 0, 0, 255, 200, 200, 200, 255]),
+        ("grayscale 1-bit", encode(ColorType::Grayscale, BitDepth::One, None, &[0b0100_0000]),
+            [0, 0, 0, 255, 255, 255, 255, 255]),
+        ("grayscale alpha", encode(ColorType::GrayscaleAlpha, BitDepth::Eight, None, &[10, 20, 30, 40]),
+            [10, 10, 10, 20, 30, 30, 30, 40]),
+        ("rgb", encode(ColorType::RGB, BitDepth::Eight, None, &[1, 2, 3, 4, 5, 6]),
+            [1, 2, 3, 255, 4, 5, 6, 255]),
+        ("rgb 16-bit", encode(ColorType::RGB, BitDepth::Sixteen, None, &[1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0]),
+            [1, 2, 3, 255, 4, 5, 6, 255]),
+        ("indexed", encode(ColorType::Indexed, BitDepth::Eight, Some((vec![9, 8, 7, 6, 5, 4], vec![128])), &[0, 1]),
+            [9, 8, 7, 128, 6, 5, 4, 255]),
+    ];
+
+    for (name, png, expected) in &cases {
+        let (width, height, pixels) = gfx::decode_png(&png[..]).unwrap_or_else(|err| panic!("{}: {}", name, err));
+        assert_eq!((width, height), (2, 1), "{}", name);
+        assert_eq!(&pixels[..], &expected[..], "{}", name);
+    }
+}